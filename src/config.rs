@@ -0,0 +1,300 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs,
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use ratatui::style::Color;
+use serde::Deserialize;
+
+/// User configuration, read from `config.toml` in the chopsticks config directory.
+///
+/// Every field has a default, so a missing file or a partial file is fine.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub keymap: KeymapPreset,
+    pub theme: Theme,
+    pub search: Search,
+    pub validation: Validation,
+    pub dedupe: Dedupe,
+    pub list: List,
+    pub layout: Layout,
+    pub clipboard: Clipboard,
+    pub jobs: Jobs,
+}
+
+/// Which set of key bindings to use.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeymapPreset {
+    #[default]
+    Default,
+    /// Modal: a normal mode for the list and an insert mode for the search bar.
+    Vim,
+    /// Readline editing in the search bar, snippet actions behind the `Ctrl-x` leader.
+    Readline,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Search {
+    pub weights: Weights,
+}
+
+/// Multipliers applied to the score of a match in each field.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Weights {
+    pub cmd: f64,
+    pub description: f64,
+    pub tags: f64,
+    pub shell: f64,
+    pub dir: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            cmd: 1.0,
+            description: 1.0,
+            tags: 1.0,
+            shell: 1.0,
+            dir: 1.0,
+        }
+    }
+}
+
+/// Checks run on the commands of a snippet when it is saved.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Validation {
+    /// Run the snippet's shell with `-n`.
+    pub syntax: bool,
+    /// Run `shellcheck`, if it is installed.
+    pub shellcheck: bool,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Self {
+            syntax: true,
+            shellcheck: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Dedupe {
+    /// How similar two commands must be, from 0.0 to 1.0, to count as duplicates.
+    pub threshold: f64,
+}
+
+impl Default for Dedupe {
+    fn default() -> Self {
+        Self { threshold: 0.9 }
+    }
+}
+
+/// How the list and the preview pane share the screen.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Layout {
+    /// Below this terminal width, the preview goes below the list instead of beside it.
+    pub stack_below: u16,
+    /// Percentage of the screen taken by the search bar and the list, the rest
+    /// is for the preview.
+    pub split: u16,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            stack_below: 100,
+            split: 50,
+        }
+    }
+}
+
+/// Snippets run in the background.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Jobs {
+    /// What happens to the running jobs when chopsticks quits.
+    pub on_quit: OnQuit,
+    /// Lines of output kept for each job.
+    pub output_lines: usize,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Self {
+            on_quit: OnQuit::default(),
+            output_lines: 1000,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnQuit {
+    /// Send them SIGTERM.
+    #[default]
+    Kill,
+    /// Let them run; their output keeps going to their log files.
+    Leave,
+}
+
+/// How copied commands get to the clipboard.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Clipboard {
+    /// Backends to try, in order, until one works.
+    pub backends: Vec<ClipboardBackend>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self {
+            backends: vec![
+                ClipboardBackend::Arboard,
+                ClipboardBackend::WlCopy,
+                ClipboardBackend::Xclip,
+                ClipboardBackend::Xsel,
+                ClipboardBackend::Osc52,
+                ClipboardBackend::Tmux,
+            ],
+        }
+    }
+}
+
+/// A way of putting text on the clipboard.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClipboardBackend {
+    /// The system clipboard, through X11, Wayland, macOS or Windows.
+    Arboard,
+    WlCopy,
+    Xclip,
+    Xsel,
+    /// The terminal's clipboard, through the OSC 52 escape sequence. Works over SSH.
+    Osc52,
+    /// The tmux paste buffer.
+    Tmux,
+}
+
+impl Display for ClipboardBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Arboard => "arboard",
+            Self::WlCopy => "wl-copy",
+            Self::Xclip => "xclip",
+            Self::Xsel => "xsel",
+            Self::Osc52 => "OSC 52",
+            Self::Tmux => "tmux",
+        })
+    }
+}
+
+/// How the snippets are listed.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct List {
+    pub layout: ListLayout,
+    /// Columns of the table layout, in order.
+    pub columns: Vec<Column>,
+}
+
+impl Default for List {
+    fn default() -> Self {
+        Self {
+            layout: ListLayout::default(),
+            columns: vec![
+                Column::Index,
+                Column::Cmd,
+                Column::Description,
+                Column::Tags,
+            ],
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ListLayout {
+    /// One command per line.
+    #[default]
+    List,
+    /// A table with the configured columns.
+    Table,
+}
+
+/// A column of the table layout.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    Index,
+    Cmd,
+    Description,
+    Tags,
+    Collection,
+    LastUsed,
+    UseCount,
+    /// Exit status of the last execution.
+    ExitStatus,
+}
+
+/// Colors used when highlighting commands.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Theme {
+    pub program: Color,
+    pub flag: Color,
+    pub string: Color,
+    pub variable: Color,
+    pub operator: Color,
+    pub comment: Color,
+    /// Chars matched by the search query.
+    pub matched: Color,
+    /// Field qualifiers like `cmd:` in the search bar.
+    pub qualifier: Color,
+    /// Background of the values filled into placeholders, in the preview.
+    pub filled: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            program: Color::Cyan,
+            flag: Color::Yellow,
+            string: Color::Green,
+            variable: Color::Magenta,
+            operator: Color::LightRed,
+            comment: Color::DarkGray,
+            matched: Color::LightYellow,
+            qualifier: Color::LightBlue,
+            filled: Color::DarkGray,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let config_path = Self::config_path();
+        if !config_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(config_path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    fn config_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap()
+            .join("chopsticks")
+            .join("config.toml")
+    }
+}
@@ -5,7 +5,7 @@ use futures::{FutureExt, StreamExt};
 use tokio::sync::mpsc;
 
 /// Terminal events.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum Event {
     /// Terminal tick.
     Tick,
@@ -13,6 +13,12 @@ pub enum Event {
     Key(KeyEvent),
     /// Mouse click/scroll.
     Mouse(MouseEvent),
+    /// Text pasted with bracketed paste, in one piece.
+    Paste(String),
+    /// New terminal size, in columns and rows.
+    Resize(u16, u16),
+    FocusGained,
+    FocusLost,
 }
 
 /// Terminal event handler.
@@ -49,15 +55,25 @@ impl EventHandler {
 
                     Some(Ok(evt)) = crossterm_event => {
                         match evt {
-                            CrosstermEvent::Key(key) => {
-                                if key.kind == crossterm::event::KeyEventKind::Press {
-                                    _sender.send(Event::Key(key)).unwrap();
-                                }
+                            CrosstermEvent::Key(key) if key.kind == crossterm::event::KeyEventKind::Press => {
+                                _sender.send(Event::Key(key)).unwrap();
                             },
 
                             CrosstermEvent::Mouse(mouse) => {
                                 _sender.send(Event::Mouse(mouse)).unwrap();
                               },
+                            CrosstermEvent::Paste(text) => {
+                                _sender.send(Event::Paste(text)).unwrap();
+                            },
+                            CrosstermEvent::Resize(columns, rows) => {
+                                _sender.send(Event::Resize(columns, rows)).unwrap();
+                            },
+                            CrosstermEvent::FocusGained => {
+                                _sender.send(Event::FocusGained).unwrap();
+                            },
+                            CrosstermEvent::FocusLost => {
+                                _sender.send(Event::FocusLost).unwrap();
+                            },
                             _ => {},
                         }
                     }
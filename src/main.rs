@@ -1,14 +1,30 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use chopsticks::tui::{self, model::App};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => {}
+        ["edit", "--all"] => return tui::edit_all(),
+        _ => bail!("Usage: chopsticks [edit --all]"),
+    }
+
     tui::install_panic_hook();
     let mut terminal = tui::init_terminal()?;
     let mut app = App::new();
 
     app.init();
     while !app.quit {
+        if app.clear_screen {
+            terminal.clear()?;
+            app.clear_screen = false;
+        }
         terminal.draw(|f| app.view(f))?;
         if let Some(msg) = app.handle_event().await {
             app.update(msg);
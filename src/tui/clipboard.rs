@@ -0,0 +1,122 @@
+use std::{
+    env,
+    io::{stdout, IsTerminal, Write},
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+use arboard::Clipboard;
+
+use crate::config::ClipboardBackend;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// The OSC 52 sequence asking the terminal to put `text` on the clipboard. Inside
+/// tmux it's wrapped to pass through to the outer terminal.
+fn osc52(text: &str) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;\x1b{sequence}\x1b\\")
+    } else {
+        sequence
+    }
+}
+
+/// Runs a clipboard tool with `text` as its input.
+fn pipe(program: &str, args: &[&str], text: &str) -> Result<()> {
+    // Output is discarded, as tools like xclip stay in the background serving the clipboard.
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{program} exited with {status}");
+    }
+    Ok(())
+}
+
+fn copy_with(backend: ClipboardBackend, text: &str) -> Result<()> {
+    match backend {
+        ClipboardBackend::Arboard => Clipboard::new()?.set_text(text)?,
+        ClipboardBackend::WlCopy => pipe("wl-copy", &[], text)?,
+        ClipboardBackend::Xclip => pipe("xclip", &["-selection", "clipboard"], text)?,
+        ClipboardBackend::Xsel => pipe("xsel", &["--clipboard", "--input"], text)?,
+        // Whether the terminal supports it can't be told, only that there is one.
+        ClipboardBackend::Osc52 => {
+            if !stdout().is_terminal() {
+                bail!("Not a terminal");
+            }
+            stdout().write_all(osc52(text).as_bytes())?;
+            stdout().flush()?;
+        }
+        ClipboardBackend::Tmux => {
+            if env::var_os("TMUX").is_none() {
+                bail!("Not inside tmux");
+            }
+            let status = Command::new("tmux")
+                .args(["set-buffer", "--", text])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .context("Failed to run tmux")?;
+            if !status.success() {
+                bail!("tmux exited with {status}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Copies `text` with the first of `backends` that works, and returns it.
+pub(super) fn copy(text: &str, backends: &[ClipboardBackend]) -> Result<ClipboardBackend> {
+    let mut errors = Vec::new();
+    for &backend in backends {
+        match copy_with(backend, text) {
+            Ok(()) => return Ok(backend),
+            Err(err) => errors.push(format!("{backend}: {err}")),
+        }
+    }
+    if errors.is_empty() {
+        bail!("No clipboard backends are configured");
+    }
+    bail!("No clipboard backend worked ({})", errors.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_pads() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"echo hi\n"), "ZWNobyBoaQo=");
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashSet;
+
+use super::model::Snippet;
+
+/// A command with runs of whitespace collapsed, so that formatting doesn't
+/// keep two commands apart.
+pub(super) fn normalize(cmd: &str) -> String {
+    cmd.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Char bigrams of `text`, for the Dice coefficient.
+fn bigrams(text: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut bigrams: Vec<_> = chars.windows(2).map(|w| (w[0], w[1])).collect();
+    bigrams.sort_unstable();
+    bigrams
+}
+
+/// Dice coefficient of the char bigrams of two sorted bigram lists: 1.0 for
+/// identical texts, 0.0 for texts without a bigram in common.
+fn similarity(a: &[(char, char)], b: &[(char, char)]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return if a == b { 1.0 } else { 0.0 };
+    }
+    let (mut i, mut j, mut common) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    2.0 * common as f64 / (a.len() + b.len()) as f64
+}
+
+/// Groups of at least two snippets whose commands are identical after
+/// normalizing whitespace, or at least `threshold` similar.
+pub(super) fn groups(snippets: &[Snippet], threshold: f64) -> Vec<Vec<usize>> {
+    let cmds: Vec<String> = snippets.iter().map(|s| normalize(&s.script())).collect();
+    let bigrams: Vec<_> = cmds.iter().map(|cmd| bigrams(cmd)).collect();
+
+    // Union-find over the snippet indices.
+    let mut parent: Vec<usize> = (0..snippets.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for a in 0..cmds.len() {
+        if cmds[a].is_empty() {
+            continue;
+        }
+        for b in a + 1..cmds.len() {
+            if cmds[b].is_empty() {
+                continue;
+            }
+            // The bigram counts alone rule most pairs out.
+            let (la, lb) = (bigrams[a].len() as f64, bigrams[b].len() as f64);
+            if 2.0 * la.min(lb) / (la + lb) < threshold && cmds[a] != cmds[b] {
+                continue;
+            }
+            if cmds[a] == cmds[b] || similarity(&bigrams[a], &bigrams[b]) >= threshold {
+                let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+                parent[ra.max(rb)] = ra.min(rb);
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of = vec![usize::MAX; snippets.len()];
+    for i in 0..snippets.len() {
+        let r = root(&mut parent, i);
+        if group_of[r] == usize::MAX {
+            group_of[r] = groups.len();
+            groups.push(Vec::new());
+        }
+        groups[group_of[r]].push(i);
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Index of a snippet other than `skip` with the same command as `snippet`,
+/// whitespace aside.
+pub(super) fn find_duplicate(
+    snippets: &[Snippet],
+    snippet: &Snippet,
+    skip: Option<usize>,
+) -> Option<usize> {
+    let cmd = normalize(&snippet.script());
+    if cmd.is_empty() {
+        return None;
+    }
+    snippets
+        .iter()
+        .enumerate()
+        .find(|&(i, other)| Some(i) != skip && normalize(&other.script()) == cmd)
+        .map(|(i, _)| i)
+}
+
+/// Merges a group into one snippet. The most used one is kept, with the
+/// longest description, the tags and placeholders of all, and their usage
+/// added up. What it lacks, it takes from the first of the others that has it.
+pub(super) fn merge(group: &[&Snippet]) -> Snippet {
+    let base = group
+        .iter()
+        .enumerate()
+        .max_by_key(|&(i, s)| (s.use_count, std::cmp::Reverse(i)))
+        .map(|(_, s)| *s)
+        .unwrap();
+    let mut merged = base.clone();
+
+    if let Some(best) = group.iter().max_by_key(|s| s.description.trim().len()) {
+        merged.description = best.description.clone();
+    }
+    let mut seen: HashSet<String> = merged.tags.iter().cloned().collect();
+    for snippet in group {
+        for tag in &snippet.tags {
+            if seen.insert(tag.clone()) {
+                merged.tags.push(tag.clone());
+            }
+        }
+        merged.shell = merged.shell.or_else(|| snippet.shell.clone());
+        merged.dir = merged.dir.or_else(|| snippet.dir.clone());
+        merged.collection = merged.collection.or_else(|| snippet.collection.clone());
+        merged.alias = merged.alias.or_else(|| snippet.alias.clone());
+        merged.category = merged.category.or_else(|| snippet.category.clone());
+        merged.target = merged.target.or(snippet.target);
+        merged.last_exit = merged.last_exit.or(snippet.last_exit);
+        // The steps run the same commands as the command, or they wouldn't be duplicates.
+        if !merged.is_runbook() && snippet.is_runbook() {
+            merged.steps = snippet.steps.clone();
+            merged.cmd.clear();
+        }
+        for placeholder in &snippet.placeholders {
+            if !merged
+                .placeholders
+                .iter()
+                .any(|p| p.name == placeholder.name)
+            {
+                merged.placeholders.push(placeholder.clone());
+            }
+        }
+    }
+    merged.priority = group.iter().map(|s| s.priority).max().unwrap_or_default();
+    merged.use_count = group.iter().map(|s| s.use_count).sum();
+    merged.created_at = group.iter().map(|s| s.created_at).min().unwrap_or_default();
+    merged.last_used_at = group.iter().filter_map(|s| s.last_used_at).max();
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tui::{
+        model::{Placeholder, Step},
+        tmux::Target,
+    };
+
+    fn snippets(cmds: &[&str]) -> Vec<Snippet> {
+        cmds.iter()
+            .map(|cmd| Snippet {
+                cmd: cmd.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn normalizes_whitespace() {
+        assert_eq!(normalize("  git\tstatus \n -s "), "git status -s");
+        assert_eq!(normalize(" \n "), "");
+        let snippets = snippets(&["git  status", "ls", "git status\n"]);
+        assert_eq!(groups(&snippets, 1.0), [[0, 2]]);
+    }
+
+    #[test]
+    fn groups_at_the_threshold() {
+        // 5 of the 5 and 6 bigrams are shared: a similarity of 10/11.
+        let snippets = snippets(&["ls -la", "ls -lah", "git status"]);
+        assert_eq!(
+            similarity(&bigrams("ls -la"), &bigrams("ls -lah")),
+            10.0 / 11.0
+        );
+        assert_eq!(groups(&snippets, 10.0 / 11.0), [[0, 1]]);
+        assert!(groups(&snippets, 0.91).is_empty());
+    }
+
+    #[test]
+    fn groups_chains_of_similar_commands() {
+        // The first and last are only 0.82 similar, but both close to the middle one.
+        let snippets = snippets(&["git status", "git status -s", "git status -sb"]);
+        assert_eq!(groups(&snippets, 0.85), [[0, 1, 2]]);
+        assert_eq!(groups(&snippets, 0.9), [[1, 2]]);
+    }
+
+    #[test]
+    fn finds_duplicates_of_other_snippets() {
+        let snippets = snippets(&["git status", "ls"]);
+        let snippet = &snippets[0];
+        assert_eq!(find_duplicate(&snippets, snippet, None), Some(0));
+        assert_eq!(find_duplicate(&snippets, snippet, Some(0)), None);
+    }
+
+    #[test]
+    fn merges_into_the_most_used() {
+        let placeholder = |name: &str, default: &str| Placeholder {
+            name: name.to_string(),
+            default: Some(default.to_string()),
+            generator: None,
+        };
+        let group = [
+            Snippet {
+                cmd: "kubectl logs <pod>".into(),
+                description: "Logs of a pod".into(),
+                tags: vec!["k8s".into()],
+                use_count: 2,
+                created_at: 30,
+                last_used_at: Some(300),
+                category: Some("k8s".into()),
+                target: Some(Target::Split),
+                last_exit: Some(1),
+                placeholders: vec![placeholder("pod", "web"), placeholder("ns", "dev")],
+                ..Default::default()
+            },
+            Snippet {
+                cmd: "kubectl  logs <pod>".into(),
+                description: "Logs".into(),
+                tags: vec!["logs".into(), "k8s".into()],
+                use_count: 5,
+                created_at: 20,
+                last_used_at: Some(100),
+                placeholders: vec![placeholder("pod", "api")],
+                ..Default::default()
+            },
+            Snippet {
+                description: "Logs of a pod, followed".into(),
+                steps: vec![Step {
+                    cmd: "kubectl logs <pod>".into(),
+                    description: String::new(),
+                }],
+                tags: vec!["debug".into(), "logs".into()],
+                use_count: 1,
+                created_at: 40,
+                ..Default::default()
+            },
+        ];
+        let merged = merge(&group.iter().collect::<Vec<_>>());
+
+        // The most used one is the base.
+        assert_eq!(merged.placeholders[0], placeholder("pod", "api"));
+        assert_eq!(merged.description, "Logs of a pod, followed");
+        assert_eq!(merged.tags, ["logs", "k8s", "debug"]);
+        assert_eq!(merged.use_count, 8);
+        assert_eq!(merged.created_at, 20);
+        assert_eq!(merged.last_used_at, Some(300));
+        // What the base lacks comes from the others.
+        assert_eq!(merged.category.as_deref(), Some("k8s"));
+        assert_eq!(merged.target, Some(Target::Split));
+        assert_eq!(merged.last_exit, Some(1));
+        assert_eq!(merged.placeholders[1], placeholder("ns", "dev"));
+        assert_eq!(merged.steps, group[2].steps);
+        assert!(merged.cmd.is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    env, fs,
+    io::Write,
+    path::Path,
+    process::{self, Command},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::Config;
+
+use super::{
+    ask, create_private,
+    model::{self, App, Snippet},
+    reference, syntax,
+};
+
+/// What to do with a store that has problems, as answered by the user.
+#[derive(Clone, Copy)]
+enum Answer {
+    Reopen,
+    Save,
+    Discard,
+}
+
+/// The user's editor: `$VISUAL`, `$EDITOR` or else `vi`.
+fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = editor();
+    // Editors are often set with arguments, like `code --wait`.
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to start the editor `{editor}`"))?;
+    if !status.success() {
+        bail!("The editor `{editor}` exited with {status}");
+    }
+    Ok(())
+}
+
+/// Opens `text` in the user's editor, in the restored terminal, and returns
+/// the text once the editor exits.
+pub(super) fn edit(text: &str) -> Result<String> {
+    let path = env::temp_dir().join(format!("chopsticks-{}.toml", process::id()));
+    create_private(&path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    let edited = run_editor(&path).and_then(|()| Ok(fs::read_to_string(&path)?));
+    let _ = fs::remove_file(&path);
+    edited
+}
+
+/// Edits a snippet until it parses, or the user rather fixes it in the built-in editor.
+pub(super) fn edit_snippet(text: &str) -> Result<String> {
+    let mut text = edit(text)?;
+    while let Err(err) = toml::from_str::<Snippet>(&text) {
+        println!("{err}");
+        let reopen = ask(
+            "Reopen the editor? [Y]es, (n)o and fix it in chopsticks:",
+            &[("y", true), ("n", false)],
+            false,
+        )?;
+        if !reopen {
+            break;
+        }
+        text = edit(&text)?;
+    }
+    Ok(text)
+}
+
+/// Problems in the commands of `snippets`, labeled with the snippet they are in.
+fn validate(snippets: &[Snippet], config: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (i, snippet) in snippets.iter().enumerate() {
+        let mut messages: Vec<String> =
+            syntax::check(&snippet.to_string(), snippet, &config.validation)
+                .into_iter()
+                .map(|warning| warning.message)
+                .collect();
+        if let Err(err) = reference::expand(snippets, snippet, &snippet.script()) {
+            messages.push(err.to_string());
+        }
+
+        let label = match snippet.description.trim() {
+            "" => snippet
+                .script()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            description => description.to_string(),
+        };
+        for message in messages {
+            problems.push(format!("Snippet {} ({label}): {message}", i + 1));
+        }
+    }
+    problems
+}
+
+/// Opens the whole store in the user's editor, and saves the result once it
+/// parses and its commands pass validation, or the user saves it anyway.
+pub fn edit_all() -> Result<()> {
+    let path = App::snippet_path();
+    let original = if path.exists() {
+        fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    let old = model::parse_store(&original).unwrap_or_default();
+    let config = Config::load()?;
+
+    let mut text = original.clone();
+    loop {
+        text = edit(&text)?;
+        if text == original {
+            println!("No changes");
+            return Ok(());
+        }
+
+        let (problems, parsed) = match model::parse_store(&text) {
+            Ok(snippets) => (validate(&snippets, &config), true),
+            Err(err) => (vec![err.to_string()], false),
+        };
+        if problems.is_empty() {
+            break;
+        }
+        for problem in &problems {
+            println!("{problem}");
+        }
+
+        let answer = if parsed {
+            ask(
+                "[R]eopen the editor, (s)ave anyway, or (d)iscard the changes:",
+                &[
+                    ("r", Answer::Reopen),
+                    ("s", Answer::Save),
+                    ("d", Answer::Discard),
+                ],
+                Answer::Discard,
+            )?
+        } else {
+            ask(
+                "[R]eopen the editor, or (d)iscard the changes:",
+                &[("r", Answer::Reopen), ("d", Answer::Discard)],
+                Answer::Discard,
+            )?
+        };
+        match answer {
+            Answer::Reopen => {}
+            Answer::Save => break,
+            Answer::Discard => {
+                println!("Discarded the changes");
+                return Ok(());
+            }
+        }
+    }
+
+    // Snippets that aren't the same as before were added or changed just now.
+    let mut snippets = model::parse_store(&text)?;
+    let now = model::now();
+    for snippet in snippets.iter_mut().filter(|snippet| !old.contains(snippet)) {
+        snippet.updated_at = now;
+        if snippet.created_at == 0 {
+            snippet.created_at = now;
+        }
+    }
+    model::assign_ids(&mut snippets);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    model::write_store(&snippets)?;
+    println!("Saved {} snippets", snippets.len());
+    Ok(())
+}
@@ -0,0 +1,466 @@
+use ratatui::{
+    style::{Style, Stylize},
+    text::{Line, Span},
+};
+
+use crate::config::Theme;
+
+/// Shells understood by the tokenizer. Snippets with any other `shell` are
+/// rendered with only strings and comments highlighted.
+const SHELLS: [&str; 6] = ["sh", "bash", "zsh", "fish", "dash", "ksh"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Plain,
+    Program,
+    Flag,
+    String,
+    Variable,
+    Operator,
+    Comment,
+}
+
+impl Kind {
+    fn style(self, theme: &Theme) -> Style {
+        match self {
+            Kind::Plain => Style::new(),
+            Kind::Program => Style::new().fg(theme.program).bold(),
+            Kind::Flag => Style::new().fg(theme.flag),
+            Kind::String => Style::new().fg(theme.string),
+            Kind::Variable => Style::new().fg(theme.variable),
+            Kind::Operator => Style::new().fg(theme.operator).bold(),
+            Kind::Comment => Style::new().fg(theme.comment).italic(),
+        }
+    }
+}
+
+/// Whether a snippet's `shell` is a shell rather than some other interpreter.
+/// Snippets without one run in the default shell.
+pub(super) fn is_shell(shell: Option<&str>) -> bool {
+    shell.is_none_or(|shell| {
+        let name = shell.rsplit('/').next().unwrap_or(shell);
+        SHELLS.contains(&name)
+    })
+}
+
+/// Highlights `cmd` line by line, picking the tokenizer from the snippet's `shell`.
+pub(super) fn highlight(cmd: &str, shell: Option<&str>, theme: &Theme) -> Vec<Line<'static>> {
+    let tokens = if is_shell(shell) {
+        tokenize_shell(cmd)
+    } else {
+        tokenize_generic(cmd)
+    };
+
+    let mut lines = vec![Line::default()];
+    for (kind, text) in tokens {
+        let mut parts = text.split('\n');
+        if let Some(first) = parts.next().filter(|s| !s.is_empty()) {
+            lines
+                .last_mut()
+                .unwrap()
+                .push_span(Span::styled(first.to_string(), kind.style(theme)));
+        }
+        for part in parts {
+            let mut line = Line::default();
+            if !part.is_empty() {
+                line.push_span(Span::styled(part.to_string(), kind.style(theme)));
+            }
+            lines.push(line);
+        }
+    }
+
+    lines
+}
+
+/// Patches the chars at `positions` with `style`. Positions count chars of the
+/// original text, newlines included, as returned by the fuzzy matcher.
+pub(super) fn emphasize(
+    lines: Vec<Line<'static>>,
+    positions: &[usize],
+    style: Style,
+) -> Vec<Line<'static>> {
+    if positions.is_empty() {
+        return lines;
+    }
+
+    let mut offset = 0;
+    lines
+        .into_iter()
+        .map(|line| {
+            let mut spans = Vec::new();
+            for span in line.spans {
+                let mut current = String::new();
+                let mut current_matched = false;
+                for c in span.content.chars() {
+                    let matched = positions.binary_search(&offset).is_ok();
+                    if matched != current_matched && !current.is_empty() {
+                        let span_style = if current_matched {
+                            span.style.patch(style)
+                        } else {
+                            span.style
+                        };
+                        spans.push(Span::styled(std::mem::take(&mut current), span_style));
+                    }
+                    current_matched = matched;
+                    current.push(c);
+                    offset += 1;
+                }
+                if !current.is_empty() {
+                    let span_style = if current_matched {
+                        span.style.patch(style)
+                    } else {
+                        span.style
+                    };
+                    spans.push(Span::styled(current, span_style));
+                }
+            }
+            // The newline that ended this line.
+            offset += 1;
+            Line::from(spans)
+        })
+        .collect()
+}
+
+fn is_operator(c: char) -> bool {
+    matches!(c, '|' | '&' | ';' | '<' | '>' | '(' | ')')
+}
+
+fn is_word_end(c: char) -> bool {
+    c.is_whitespace() || is_operator(c) || matches!(c, '\'' | '"' | '$' | '`')
+}
+
+fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// Splits a shell command into highlighted tokens. Quotes may span lines.
+fn tokenize_shell(cmd: &str) -> Vec<(Kind, String)> {
+    let chars: Vec<char> = cmd.chars().collect();
+    let mut tokens = Vec::new();
+    // Whether the next word is in command position.
+    let mut expect_program = true;
+    // Whether the previous char separates words.
+    let mut word_start = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let kind = if c.is_whitespace() {
+            while i < chars.len() && chars[i].is_whitespace() {
+                if chars[i] == '\n' {
+                    expect_program = true;
+                }
+                i += 1;
+            }
+            word_start = true;
+            tokens.push((Kind::Plain, chars[start..i].iter().collect()));
+            continue;
+        } else if c == '#' && word_start {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            Kind::Comment
+        } else if c == '\'' {
+            i += 1;
+            while i < chars.len() && chars[i] != '\'' {
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+            Kind::String
+        } else if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+            Kind::String
+        } else if c == '$' {
+            i += 1;
+            match chars.get(i) {
+                Some('{') => {
+                    while i < chars.len() && chars[i] != '}' {
+                        i += 1;
+                    }
+                    i = (i + 1).min(chars.len());
+                }
+                Some('(') => {
+                    i += 1;
+                    expect_program = true;
+                }
+                Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+                    while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                        i += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_digit() || "@*#?$!-".contains(*c) => i += 1,
+                _ => {}
+            }
+            if chars[start..i].ends_with(&['(']) {
+                Kind::Operator
+            } else {
+                Kind::Variable
+            }
+        } else if is_operator(c) || c == '`' {
+            while i < chars.len() && (is_operator(chars[i]) || chars[i] == '`') {
+                i += 1;
+            }
+            // Redirections are followed by a file name, separators by a command.
+            let op = &chars[start..i];
+            expect_program = match op {
+                [')'] => false,
+                ['&'] | ['&', '&'] => true,
+                _ => !op.iter().all(|c| matches!(c, '<' | '>' | '&')),
+            };
+            Kind::Operator
+        } else {
+            // A bare word. The fd number of a redirection like `2>` lands here too.
+            while i < chars.len() && !is_word_end(chars[i]) {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            i = i.min(chars.len());
+            let word: String = chars[start..i].iter().collect();
+            if word_start && expect_program {
+                if is_assignment(&word) {
+                    Kind::Variable
+                } else {
+                    expect_program = false;
+                    Kind::Program
+                }
+            } else if word_start && word.starts_with('-') {
+                Kind::Flag
+            } else {
+                Kind::Plain
+            }
+        };
+
+        word_start = kind == Kind::Operator;
+        tokens.push((kind, chars[start..i].iter().collect()));
+    }
+
+    tokens
+}
+
+/// Highlights only quoted strings and `#`/`//` comments, for non-shell languages.
+fn tokenize_generic(cmd: &str) -> Vec<(Kind, String)> {
+    let chars: Vec<char> = cmd.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let kind = if c == '#' || (c == '/' && chars.get(i + 1) == Some(&'/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            Kind::Comment
+        } else if c == '\'' || c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+            Kind::String
+        } else {
+            while i < chars.len()
+                && !matches!(chars[i], '#' | '\'' | '"')
+                && !(chars[i] == '/' && chars.get(i + 1) == Some(&'/'))
+            {
+                i += 1;
+            }
+            Kind::Plain
+        };
+
+        tokens.push((kind, chars[start..i].iter().collect()));
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Kind::{Comment, Flag, Operator, Plain, Program, Variable};
+
+    fn assert_tokens(tokens: Vec<(Kind, String)>, expected: &[(Kind, &str)]) {
+        let expected: Vec<(Kind, String)> = expected
+            .iter()
+            .map(|&(kind, text)| (kind, text.to_string()))
+            .collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn tokenizes_quotes_and_comments() {
+        assert_tokens(
+            tokenize_shell(r#"git commit -m "fix \"x\"" # note"#),
+            &[
+                (Program, "git"),
+                (Plain, " "),
+                (Plain, "commit"),
+                (Plain, " "),
+                (Flag, "-m"),
+                (Plain, " "),
+                (Kind::String, r#""fix \"x\"""#),
+                (Plain, " "),
+                (Comment, "# note"),
+            ],
+        );
+        // A `#` inside a word doesn't start a comment, and quotes span lines.
+        assert_tokens(
+            tokenize_shell("echo a#b 'c\nd'\nls"),
+            &[
+                (Program, "echo"),
+                (Plain, " "),
+                (Plain, "a#b"),
+                (Plain, " "),
+                (Kind::String, "'c\nd'"),
+                (Plain, "\n"),
+                (Program, "ls"),
+            ],
+        );
+    }
+
+    #[test]
+    fn tokenizes_variables_and_substitutions() {
+        assert_tokens(
+            tokenize_shell("FOO=1 echo $HOME ${X:-y} $(date +%s) $1"),
+            &[
+                (Variable, "FOO=1"),
+                (Plain, " "),
+                (Program, "echo"),
+                (Plain, " "),
+                (Variable, "$HOME"),
+                (Plain, " "),
+                (Variable, "${X:-y}"),
+                (Plain, " "),
+                (Operator, "$("),
+                (Program, "date"),
+                (Plain, " "),
+                (Plain, "+%s"),
+                (Operator, ")"),
+                (Plain, " "),
+                (Variable, "$1"),
+            ],
+        );
+    }
+
+    #[test]
+    fn tokenizes_escapes_and_operators() {
+        assert_tokens(
+            tokenize_shell(r#"a\ b 2>/dev/null | grep 'x y' && echo "$v""#),
+            &[
+                (Program, r"a\ b"),
+                (Plain, " "),
+                (Plain, "2"),
+                (Operator, ">"),
+                (Plain, "/dev/null"),
+                (Plain, " "),
+                (Operator, "|"),
+                (Plain, " "),
+                (Program, "grep"),
+                (Plain, " "),
+                (Kind::String, "'x y'"),
+                (Plain, " "),
+                (Operator, "&&"),
+                (Plain, " "),
+                (Program, "echo"),
+                (Plain, " "),
+                (Kind::String, r#""$v""#),
+            ],
+        );
+    }
+
+    #[test]
+    fn tokenizes_other_languages() {
+        assert_tokens(
+            tokenize_generic("print(\"a # b\")  # done\nx = 'it\\'s' // c"),
+            &[
+                (Plain, "print("),
+                (Kind::String, "\"a # b\""),
+                (Plain, ")  "),
+                (Comment, "# done"),
+                (Plain, "\nx = "),
+                (Kind::String, r"'it\'s'"),
+                (Plain, " "),
+                (Comment, "// c"),
+            ],
+        );
+    }
+
+    #[test]
+    fn picks_the_tokenizer_from_the_shell() {
+        assert!(is_shell(None));
+        assert!(is_shell(Some("/usr/bin/zsh")));
+        assert!(!is_shell(Some("python3")));
+
+        let theme = Theme::default();
+        let python = highlight("ls -la\n# x", Some("python3"), &theme);
+        assert_eq!(python[0], Line::from("ls -la"));
+        assert_eq!(
+            python[1],
+            Line::from(Span::styled("# x", Comment.style(&theme)))
+        );
+        let shell = highlight("ls -la", None, &theme);
+        assert_eq!(shell[0].spans[2], Span::styled("-la", Flag.style(&theme)));
+    }
+
+    #[test]
+    fn emphasizes_across_spans_and_lines() {
+        let theme = Theme::default();
+        let lines = highlight("ls 'héllo wörld'\necho ü", None, &theme);
+        let matched = Style::new().underlined();
+        // `s` and the space, `hé` and `ör` on the first line, `ü` on the second.
+        let lines = emphasize(lines, &[1, 2, 4, 5, 11, 12, 22], matched);
+
+        let span = |text: &'static str, kind: Kind, emphasized: bool| {
+            let style = kind.style(&theme);
+            Span::styled(
+                text,
+                if emphasized {
+                    style.patch(matched)
+                } else {
+                    style
+                },
+            )
+        };
+        assert_eq!(
+            lines[0].spans,
+            [
+                span("l", Program, false),
+                span("s", Program, true),
+                span(" ", Plain, true),
+                span("'", Kind::String, false),
+                span("hé", Kind::String, true),
+                span("llo w", Kind::String, false),
+                span("ör", Kind::String, true),
+                span("ld'", Kind::String, false),
+            ]
+        );
+        assert_eq!(
+            lines[1].spans,
+            [
+                span("echo", Program, false),
+                span(" ", Plain, false),
+                span("ü", Plain, true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::VecDeque,
+    env,
+    fs::{self, File},
+    io::Write,
+    path::PathBuf,
+    process::{self, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::{Child, Command},
+    time,
+};
+
+use super::{create_private, model::Snippet};
+
+/// How often a running job's log is read.
+const POLL: Duration = Duration::from_millis(100);
+
+/// Numbers the files of the jobs started by this process.
+static SPAWNED: AtomicUsize = AtomicUsize::new(0);
+
+/// A signal to send to a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Interrupt,
+    Terminate,
+    Kill,
+}
+
+/// What the task watching a job shares with the TUI.
+#[derive(Debug, Default)]
+struct Shared {
+    /// The last lines of output, oldest first.
+    output: VecDeque<String>,
+    /// When the job exited, and its exit code (`None` if killed by a signal).
+    finished: Option<(Instant, Option<i32>)>,
+}
+
+/// A snippet running in the background, or that ran there.
+#[derive(Debug)]
+pub(super) struct Job {
+    /// The filled-in snippet, to run again on restart.
+    pub snippet: Snippet,
+    /// Index of the snippet in the store, to record its exit code. `None`
+    /// once the snippet was removed.
+    pub index: Option<usize>,
+    pub pid: u32,
+    started: Instant,
+    /// Where the output goes, so it has somewhere to go after chopsticks quits.
+    pub log: PathBuf,
+    /// The command as a script of the snippet's shell.
+    script: PathBuf,
+    shared: Arc<Mutex<Shared>>,
+    /// Whether the status line told that the job finished.
+    reported: bool,
+    /// When a job stopped to be restarted is killed if it's still running.
+    pub restart_by: Option<Instant>,
+}
+
+impl Job {
+    /// Starts `snippet`, the one at `index` in the store, in its own process
+    /// group, keeping the last `lines` of its output.
+    pub fn spawn(index: Option<usize>, snippet: Snippet, lines: usize) -> Result<Self> {
+        if snippet.is_runbook() {
+            bail!("Runbooks need confirmation at each step, so they can't run in the background");
+        }
+
+        // Like `execute_cmd`, the command runs as a script file of the snippet's shell.
+        let name = format!(
+            "chopsticks-job-{}-{}",
+            process::id(),
+            SPAWNED.fetch_add(1, Ordering::Relaxed)
+        );
+        let script = env::temp_dir().join(format!("{name}.sh"));
+        let log = env::temp_dir().join(format!("{name}.log"));
+        create_private(&script)
+            .and_then(|mut file| file.write_all(snippet.cmd.as_bytes()))
+            .with_context(|| format!("Failed to write {}", script.display()))?;
+        let output =
+            create_private(&log).with_context(|| format!("Failed to create {}", log.display()))?;
+        let reader = tokio::fs::File::from_std(File::open(&log)?);
+
+        let runner = snippet.shell.as_deref().unwrap_or("sh");
+        let mut command = Command::new(runner);
+        command
+            .arg(&script)
+            .stdin(Stdio::null())
+            .stdout(output.try_clone()?)
+            .stderr(output);
+        // Signals go to everything the job starts, and a Ctrl-c in the
+        // terminal after chopsticks quits doesn't reach jobs left running.
+        #[cfg(unix)]
+        command.process_group(0);
+        let child = command
+            .spawn()
+            .with_context(|| format!("Failed to run {runner}"))?;
+
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let pid = child.id().unwrap_or_default();
+        tokio::spawn(watch(
+            child,
+            BufReader::new(reader),
+            script.clone(),
+            Arc::clone(&shared),
+            lines,
+        ));
+
+        Ok(Self {
+            snippet,
+            index,
+            pid,
+            started: Instant::now(),
+            log,
+            script,
+            shared,
+            reported: false,
+            restart_by: None,
+        })
+    }
+
+    /// The exit code, once the job finished. `Some(None)` if it was killed by a signal.
+    pub fn exit(&self) -> Option<Option<i32>> {
+        self.shared.lock().unwrap().finished.map(|(_, code)| code)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.exit().is_none()
+    }
+
+    /// How long the job ran, or has been running.
+    pub fn runtime(&self) -> Duration {
+        let finished = self.shared.lock().unwrap().finished.map(|(at, _)| at);
+        finished.unwrap_or_else(Instant::now) - self.started
+    }
+
+    pub fn output(&self) -> Vec<String> {
+        self.shared.lock().unwrap().output.iter().cloned().collect()
+    }
+
+    /// The exit code the first time it's asked for after the job finished.
+    pub fn take_exit(&mut self) -> Option<Option<i32>> {
+        if self.reported {
+            return None;
+        }
+        let exit = self.exit()?;
+        self.reported = true;
+        Some(exit)
+    }
+
+    /// Sends `signal` to the job's process group.
+    pub fn signal(&self, signal: Signal) -> Result<()> {
+        if !self.is_running() {
+            bail!("The job isn't running");
+        }
+        #[cfg(unix)]
+        {
+            let signal = match signal {
+                Signal::Interrupt => libc::SIGINT,
+                Signal::Terminate => libc::SIGTERM,
+                Signal::Kill => libc::SIGKILL,
+            };
+            // This is safe. `kill` only reads its arguments.
+            if unsafe { libc::kill(-(self.pid as libc::pid_t), signal) } != 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = signal;
+            bail!("Signals can only be sent to jobs on Unix")
+        }
+    }
+
+    pub fn remove_log(&self) {
+        let _ = fs::remove_file(&self.log);
+    }
+
+    /// Removes the script, which the watching task does once the job exits,
+    /// for a job left running. The shell keeps reading the file it opened.
+    pub fn remove_script(&self) {
+        let _ = fs::remove_file(&self.script);
+    }
+}
+
+/// Finds escape sequences, which would garble the screen, and the text a
+/// carriage return goes back over.
+fn escape_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"\x1b(\[[0-?]*[ -/]*[@-~]|\][^\x07\x1b]*(\x07|\x1b\\)|.)|^.*\r").unwrap()
+    })
+}
+
+/// A line of output as it looks in a terminal, without colors or other escapes.
+fn clean(line: &[u8]) -> String {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim_end_matches(['\n', '\r']);
+    escape_pattern()
+        .replace_all(line, "")
+        .replace('\t', "    ")
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect()
+}
+
+/// Moves the complete lines written to the log since the last call into the output.
+async fn follow(
+    reader: &mut BufReader<tokio::fs::File>,
+    partial: &mut Vec<u8>,
+    shared: &Mutex<Shared>,
+    lines: usize,
+) {
+    // At the end of the log, a line may still be partly written.
+    while let Ok(1..) = reader.read_until(b'\n', partial).await {
+        if partial.ends_with(b"\n") {
+            push(shared, clean(partial), lines);
+            partial.clear();
+        }
+    }
+}
+
+fn push(shared: &Mutex<Shared>, line: String, lines: usize) {
+    let mut shared = shared.lock().unwrap();
+    shared.output.push_back(line);
+    while shared.output.len() > lines {
+        shared.output.pop_front();
+    }
+}
+
+/// Follows the log of a job until it exits, then records its exit.
+async fn watch(
+    mut child: Child,
+    mut reader: BufReader<tokio::fs::File>,
+    script: PathBuf,
+    shared: Arc<Mutex<Shared>>,
+    lines: usize,
+) {
+    let mut partial = Vec::new();
+    let status = loop {
+        tokio::select! {
+            status = child.wait() => break status,
+            _ = time::sleep(POLL) => follow(&mut reader, &mut partial, &shared, lines).await,
+        }
+    };
+    follow(&mut reader, &mut partial, &shared, lines).await;
+    if !partial.is_empty() {
+        push(&shared, clean(&partial), lines);
+    }
+    let _ = fs::remove_file(&script);
+
+    let code = status.ok().and_then(|status| status.code());
+    shared.lock().unwrap().finished = Some((Instant::now(), code));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleans_escapes() {
+        assert_eq!(clean(b"\x1b[1;31merror\x1b[0m: bad\n"), "error: bad");
+        assert_eq!(clean(b"10%\r50%\r100%\r\n"), "100%");
+        assert_eq!(clean(b"\x1b]0;title\x07a\tb"), "a    b");
+    }
+
+    #[tokio::test]
+    async fn keeps_the_last_lines() {
+        let snippet = Snippet {
+            cmd: "seq 5; printf done; exit 3".to_string(),
+            ..Default::default()
+        };
+        let job = Job::spawn(None, snippet, 3).unwrap();
+        while job.is_running() {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(job.exit(), Some(Some(3)));
+        assert_eq!(job.output(), ["4", "5", "done"]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&job.log).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        job.remove_log();
+    }
+
+    #[tokio::test]
+    async fn removes_the_script() {
+        let snippet = Snippet {
+            cmd: "echo started; sleep 0.3; echo still running".to_string(),
+            ..Default::default()
+        };
+        let job = Job::spawn(None, snippet, 10).unwrap();
+        while job.output().is_empty() {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        // As when chopsticks quits and leaves the job running.
+        job.remove_script();
+        assert!(!job.script.exists());
+        while job.is_running() {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(job.exit(), Some(Some(0)));
+        assert_eq!(job.output(), ["started", "still running"]);
+        job.remove_log();
+    }
+}
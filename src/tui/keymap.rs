@@ -0,0 +1,702 @@
+use std::fmt::Display;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::config::KeymapPreset;
+
+/// The part of the UI that has the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Typing into the search bar.
+    List,
+    /// Vim normal mode on the list.
+    Normal,
+    /// Typing into the search bar, with the category tree in place of the list.
+    Tree,
+    Editor,
+    Prompt,
+    /// Filling in the placeholders of a command.
+    Fill,
+    /// Merging duplicate snippets.
+    Dedupe,
+    /// The jobs panel.
+    Jobs,
+    Help,
+}
+
+impl Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Mode::List => "List",
+            Mode::Normal => "Normal",
+            Mode::Tree => "Tree",
+            Mode::Editor => "Editor",
+            Mode::Prompt => "Dialog",
+            Mode::Fill => "Placeholder",
+            Mode::Dedupe => "Duplicates",
+            Mode::Jobs => "Jobs",
+            Mode::Help => "Help",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Everything a key can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    SelectNext,
+    SelectPrev,
+    SelectFirst,
+    SelectLast,
+    /// Leave vim normal mode for the search bar.
+    FocusSearch,
+    /// Leave the search bar for vim normal mode.
+    NormalMode,
+    Execute,
+    Copy,
+    Add,
+    Edit,
+    Remove,
+    ToggleSearchMode,
+    /// Cycle the order of the list while the search bar is empty.
+    CycleSortOrder,
+    ToggleMark,
+    MarkAll,
+    InvertMarks,
+    AddTag,
+    RemoveTag,
+    MoveToCollection,
+    MoveToCategory,
+    RenameCategory,
+    /// Switch between the flat list and the category tree.
+    ToggleTree,
+    /// Show or hide the preview pane.
+    TogglePreview,
+    /// Expand the selected category of the tree.
+    Expand,
+    /// Collapse the selected category of the tree.
+    Collapse,
+    Export,
+    /// Open the view of duplicate snippets.
+    Dedupe,
+    /// Merge the selected group of duplicates.
+    Merge,
+    /// Run the snippet as a job, in the background.
+    RunInBackground,
+    /// Run the snippet in the tmux pane chopsticks was opened from.
+    SendToPane,
+    /// Run the snippet in a new tmux pane.
+    RunInSplit,
+    /// Run the snippet in a new tmux window.
+    RunInWindow,
+    /// Open the jobs panel.
+    Jobs,
+    /// Send SIGINT to the selected job.
+    Interrupt,
+    /// Send SIGTERM to the selected job.
+    Terminate,
+    /// Run the selected job again, stopping it first if it's running.
+    Restart,
+    /// Edit the snippet in `$VISUAL` or `$EDITOR`.
+    ExternalEdit,
+    Save,
+    Cancel,
+    Submit,
+    /// Select a page further down the list.
+    PageDown,
+    PageUp,
+    /// Scroll the details pane, or the help.
+    ScrollDown,
+    ScrollUp,
+    ScrollPageDown,
+    ScrollPageUp,
+    Help,
+    // Search bar editing.
+    LineStart,
+    LineEnd,
+    CharBack,
+    CharForward,
+    WordBack,
+    WordForward,
+    DeleteWordBack,
+    DeleteWordForward,
+    DeleteToLineStart,
+    DeleteToLineEnd,
+}
+
+/// A key press, compared ignoring the case of control and alt chords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Parses names like `Ctrl-a`, `Alt-Enter`, `F1`, `PageDown` or a single char.
+    fn parse(name: &str) -> Self {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = name;
+        loop {
+            if let Some(r) = rest.strip_prefix("Ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("Alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("Shift-") {
+                modifiers |= KeyModifiers::SHIFT;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+
+        let code = match rest {
+            "Enter" => KeyCode::Enter,
+            "Esc" => KeyCode::Esc,
+            "Tab" => KeyCode::Tab,
+            "Backspace" => KeyCode::Backspace,
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "Space" => KeyCode::Char(' '),
+            f if f.len() > 1 && f.starts_with('F') => KeyCode::F(f[1..].parse().unwrap()),
+            c => KeyCode::Char(c.chars().next().unwrap()),
+        };
+
+        Self { code, modifiers }.normalized()
+    }
+
+    /// Lowercases control and alt chords and drops the shift implied by a char's case,
+    /// so that `Ctrl-A` and `Ctrl-a`, or `G` with and without shift, are the same key.
+    fn normalized(mut self) -> Self {
+        if let KeyCode::Char(c) = self.code {
+            if self
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+            {
+                self.code = KeyCode::Char(c.to_ascii_lowercase());
+            }
+            self.modifiers -= KeyModifiers::SHIFT;
+        }
+        self
+    }
+
+    /// Whether this key types a char when it isn't bound.
+    pub fn is_printable(self) -> bool {
+        matches!(self.code, KeyCode::Char(_))
+            && !self
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(evt: KeyEvent) -> Self {
+        Self {
+            code: evt.code,
+            modifiers: evt.modifiers,
+        }
+        .normalized()
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// When a binding is shown in the bottom bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hint {
+    Never,
+    Always,
+    /// Only while snippets are marked.
+    WhenMarked,
+}
+
+#[derive(Debug, Clone)]
+pub struct Binding {
+    /// Alternative key sequences, most of them a single key.
+    pub keys: Vec<Vec<Key>>,
+    pub action: Action,
+    pub description: &'static str,
+    pub hint: Hint,
+}
+
+impl Binding {
+    /// `keys` are alternatives; each is a space separated sequence like `g g`.
+    fn new(keys: &[&str], action: Action, description: &'static str) -> Self {
+        Self {
+            keys: keys
+                .iter()
+                .map(|seq| seq.split(' ').map(Key::parse).collect())
+                .collect(),
+            action,
+            description,
+            hint: Hint::Never,
+        }
+    }
+
+    fn hint(mut self) -> Self {
+        self.hint = Hint::Always;
+        self
+    }
+
+    fn hint_when_marked(mut self) -> Self {
+        self.hint = Hint::WhenMarked;
+        self
+    }
+
+    /// Names of the key sequences, like `Ctrl-a` or `gg`.
+    pub fn labels(&self) -> Vec<String> {
+        self.keys
+            .iter()
+            .map(|seq| {
+                let keys: Vec<String> = seq.iter().map(Key::to_string).collect();
+                if keys.iter().all(|k| k.chars().count() == 1) {
+                    keys.concat()
+                } else {
+                    keys.join(" ")
+                }
+            })
+            .collect()
+    }
+}
+
+/// Result of looking up the keys pressed so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    Action(Action),
+    /// The keys start a longer sequence.
+    Pending,
+    Unbound,
+}
+
+/// Key bindings of every mode. The handlers, the bottom bar and the help
+/// overlay all read from here, so they can't disagree.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    pub list: Vec<Binding>,
+    pub normal: Vec<Binding>,
+    pub tree: Vec<Binding>,
+    pub editor: Vec<Binding>,
+    pub prompt: Vec<Binding>,
+    pub fill: Vec<Binding>,
+    pub dedupe: Vec<Binding>,
+    pub jobs: Vec<Binding>,
+    pub help: Vec<Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset(KeymapPreset::Default)
+    }
+}
+
+/// Bulk actions on marked snippets, shared by the default and vim presets.
+fn bulk_bindings() -> Vec<Binding> {
+    use Action::*;
+    vec![
+        Binding::new(&["Alt-t"], AddTag, "Add tag").hint_when_marked(),
+        Binding::new(&["Alt-u"], RemoveTag, "Remove tag").hint_when_marked(),
+        Binding::new(&["Alt-m"], MoveToCollection, "Move to collection").hint_when_marked(),
+        Binding::new(&["Alt-c"], MoveToCategory, "Move to category").hint_when_marked(),
+        Binding::new(&["Alt-x"], Export, "Export").hint_when_marked(),
+        Binding::new(&["Alt-i"], InvertMarks, "Invert marks").hint_when_marked(),
+        Binding::new(&["Alt-a"], MarkAll, "Mark all listed"),
+        Binding::new(&["Tab"], ToggleMark, "Mark"),
+    ]
+}
+
+/// Keys scrolling the details pane, the same in the list of every preset.
+fn details_bindings() -> Vec<Binding> {
+    use Action::*;
+    vec![
+        Binding::new(&["Shift-Up"], ScrollUp, "Scroll details up"),
+        Binding::new(&["Shift-Down"], ScrollDown, "Scroll details down"),
+        Binding::new(&["Shift-PageUp"], ScrollPageUp, "Page details up"),
+        Binding::new(&["Shift-PageDown"], ScrollPageDown, "Page details down"),
+    ]
+}
+
+impl Keymap {
+    pub fn preset(preset: KeymapPreset) -> Self {
+        use Action::*;
+        let (list, normal) = match preset {
+            KeymapPreset::Default => {
+                let mut list = vec![
+                    Binding::new(&["Enter"], Execute, "Execute").hint(),
+                    Binding::new(&["Ctrl-Enter"], Copy, "Copy").hint(),
+                    Binding::new(&["Ctrl-b"], RunInBackground, "Run in background"),
+                    Binding::new(&["Alt-p"], SendToPane, "Run in the tmux pane below"),
+                    Binding::new(&["Alt-s"], RunInSplit, "Run in a new tmux split"),
+                    Binding::new(&["Alt-w"], RunInWindow, "Run in a new tmux window"),
+                    Binding::new(&["Ctrl-r"], Remove, "Remove").hint(),
+                ];
+                list.extend(bulk_bindings());
+                list.extend([
+                    Binding::new(&["Ctrl-a"], Add, "Add").hint(),
+                    Binding::new(&["Ctrl-e"], Edit, "Edit").hint(),
+                    Binding::new(&["Alt-e"], ExternalEdit, "Edit in $EDITOR"),
+                    Binding::new(&["Ctrl-d"], Dedupe, "Find duplicates"),
+                    Binding::new(&["Alt-j"], Jobs, "Jobs"),
+                    Binding::new(&["Ctrl-o"], ToggleTree, "Toggle category tree"),
+                    Binding::new(&["Ctrl-p"], TogglePreview, "Toggle preview"),
+                    Binding::new(&["Up"], SelectPrev, "Select previous"),
+                    Binding::new(&["Down"], SelectNext, "Select next"),
+                    Binding::new(&["PageUp"], PageUp, "Page up"),
+                    Binding::new(&["PageDown"], PageDown, "Page down"),
+                    // Plain `Home`/`End` move the cursor in the search bar.
+                    Binding::new(&["Ctrl-Home"], SelectFirst, "Select first"),
+                    Binding::new(&["Ctrl-End"], SelectLast, "Select last"),
+                ]);
+                list.extend(details_bindings());
+                list.extend([
+                    Binding::new(&["Ctrl-t"], ToggleSearchMode, "Cycle search mode"),
+                    Binding::new(&["Ctrl-s"], CycleSortOrder, "Cycle sort order"),
+                    Binding::new(&["F1"], Help, "Help").hint(),
+                    Binding::new(&["Esc", "Ctrl-c"], Quit, "Quit").hint(),
+                ]);
+                (list, Vec::new())
+            }
+            KeymapPreset::Vim => {
+                let mut list = vec![
+                    Binding::new(&["Esc"], NormalMode, "Normal mode").hint(),
+                    Binding::new(&["Enter"], Execute, "Execute").hint(),
+                    Binding::new(&["Ctrl-Enter"], Copy, "Copy"),
+                    Binding::new(&["Alt-e"], ExternalEdit, "Edit in $EDITOR"),
+                    Binding::new(&["Alt-j"], Jobs, "Jobs"),
+                    Binding::new(&["Up"], SelectPrev, "Select previous"),
+                    Binding::new(&["Down"], SelectNext, "Select next"),
+                    Binding::new(&["PageUp"], PageUp, "Page up"),
+                    Binding::new(&["PageDown"], PageDown, "Page down"),
+                ];
+                list.extend(details_bindings());
+                list.extend([
+                    Binding::new(&["Tab"], ToggleMark, "Mark"),
+                    Binding::new(&["Ctrl-t"], ToggleSearchMode, "Cycle search mode"),
+                    Binding::new(&["Ctrl-s"], CycleSortOrder, "Cycle sort order"),
+                    Binding::new(&["Ctrl-w"], DeleteWordBack, "Delete word before cursor"),
+                    Binding::new(&["Ctrl-u"], DeleteToLineStart, "Delete to line start"),
+                    Binding::new(&["F1"], Help, "Help").hint(),
+                    Binding::new(&["Ctrl-c"], Quit, "Quit").hint(),
+                ]);
+                let mut normal = vec![
+                    Binding::new(&["Enter"], Execute, "Execute").hint(),
+                    Binding::new(&["y y"], Copy, "Copy").hint(),
+                    Binding::new(&["&"], RunInBackground, "Run in background"),
+                    Binding::new(&["T p"], SendToPane, "Run in the tmux pane below"),
+                    Binding::new(&["T s"], RunInSplit, "Run in a new tmux split"),
+                    Binding::new(&["T w"], RunInWindow, "Run in a new tmux window"),
+                    Binding::new(&["d d"], Remove, "Remove").hint(),
+                ];
+                normal.extend(bulk_bindings());
+                normal.extend([
+                    Binding::new(&["o"], Add, "Add").hint(),
+                    Binding::new(&["e"], Edit, "Edit").hint(),
+                    Binding::new(&["E"], ExternalEdit, "Edit in $EDITOR"),
+                    Binding::new(&["D"], Dedupe, "Find duplicates"),
+                    Binding::new(&["J"], Jobs, "Jobs"),
+                    Binding::new(&["t"], ToggleTree, "Toggle category tree"),
+                    Binding::new(&["p"], TogglePreview, "Toggle preview"),
+                    Binding::new(&["l", "Right"], Expand, "Expand category"),
+                    Binding::new(&["h", "Left"], Collapse, "Collapse category"),
+                    Binding::new(&["r"], RenameCategory, "Rename category"),
+                    Binding::new(&["/", "i"], FocusSearch, "Search").hint(),
+                    Binding::new(&["j", "Down"], SelectNext, "Select next"),
+                    Binding::new(&["k", "Up"], SelectPrev, "Select previous"),
+                    Binding::new(&["g g", "Home"], SelectFirst, "Select first"),
+                    Binding::new(&["G", "End"], SelectLast, "Select last"),
+                    Binding::new(&["Ctrl-b", "PageUp"], PageUp, "Page up"),
+                    Binding::new(&["Ctrl-f", "PageDown"], PageDown, "Page down"),
+                    Binding::new(&["Ctrl-y", "Shift-Up"], ScrollUp, "Scroll details up"),
+                    Binding::new(&["Ctrl-e", "Shift-Down"], ScrollDown, "Scroll details down"),
+                    Binding::new(&["Shift-PageUp"], ScrollPageUp, "Page details up"),
+                    Binding::new(&["Shift-PageDown"], ScrollPageDown, "Page details down"),
+                    Binding::new(&["Ctrl-t"], ToggleSearchMode, "Cycle search mode"),
+                    Binding::new(&["s"], CycleSortOrder, "Cycle sort order"),
+                    Binding::new(&["?", "F1"], Help, "Help").hint(),
+                    Binding::new(&["q", "Ctrl-c"], Quit, "Quit").hint(),
+                ]);
+                (list, normal)
+            }
+            KeymapPreset::Readline => {
+                let mut list = vec![
+                    Binding::new(&["Enter"], Execute, "Execute").hint(),
+                    Binding::new(&["Ctrl-Enter", "Ctrl-x y"], Copy, "Copy").hint(),
+                    Binding::new(&["Ctrl-x &"], RunInBackground, "Run in background"),
+                    Binding::new(&["Ctrl-x T p"], SendToPane, "Run in the tmux pane below"),
+                    Binding::new(&["Ctrl-x T s"], RunInSplit, "Run in a new tmux split"),
+                    Binding::new(&["Ctrl-x T w"], RunInWindow, "Run in a new tmux window"),
+                    Binding::new(&["Ctrl-x d"], Remove, "Remove").hint(),
+                    Binding::new(&["Ctrl-x t"], AddTag, "Add tag").hint_when_marked(),
+                    Binding::new(&["Ctrl-x u"], RemoveTag, "Remove tag").hint_when_marked(),
+                    Binding::new(&["Ctrl-x m"], MoveToCollection, "Move to collection")
+                        .hint_when_marked(),
+                    Binding::new(&["Ctrl-x o"], Export, "Export").hint_when_marked(),
+                    Binding::new(&["Ctrl-x i"], InvertMarks, "Invert marks").hint_when_marked(),
+                    Binding::new(&["Ctrl-x h"], MarkAll, "Mark all listed"),
+                    Binding::new(&["Tab"], ToggleMark, "Mark"),
+                    Binding::new(&["Ctrl-x a"], Add, "Add").hint(),
+                    Binding::new(&["Ctrl-x e"], Edit, "Edit").hint(),
+                    Binding::new(&["Ctrl-x Ctrl-e"], ExternalEdit, "Edit in $EDITOR"),
+                    Binding::new(&["Ctrl-x D"], Dedupe, "Find duplicates"),
+                    Binding::new(&["Ctrl-x j"], Jobs, "Jobs"),
+                    Binding::new(&["Ctrl-x v"], ToggleTree, "Toggle category tree"),
+                    Binding::new(&["Ctrl-x p"], TogglePreview, "Toggle preview"),
+                    Binding::new(&["Ctrl-x c"], MoveToCategory, "Move to category")
+                        .hint_when_marked(),
+                    Binding::new(&["Up", "Ctrl-p"], SelectPrev, "Select previous"),
+                    // The search bar always searches, so a reverse search goes to the next match.
+                    Binding::new(&["Down", "Ctrl-n", "Ctrl-r"], SelectNext, "Select next"),
+                    Binding::new(&["PageUp"], PageUp, "Page up"),
+                    Binding::new(&["PageDown"], PageDown, "Page down"),
+                    Binding::new(&["Alt-<"], SelectFirst, "Select first"),
+                    Binding::new(&["Alt->"], SelectLast, "Select last"),
+                ];
+                list.extend(details_bindings());
+                list.extend([
+                    Binding::new(&["Ctrl-x s"], ToggleSearchMode, "Cycle search mode"),
+                    Binding::new(&["Ctrl-x S"], CycleSortOrder, "Cycle sort order"),
+                    Binding::new(&["Ctrl-a", "Home"], LineStart, "Move to line start"),
+                    Binding::new(&["Ctrl-e", "End"], LineEnd, "Move to line end"),
+                    Binding::new(&["Ctrl-b", "Left"], CharBack, "Move back a char"),
+                    Binding::new(&["Ctrl-f", "Right"], CharForward, "Move forward a char"),
+                    Binding::new(&["Alt-b"], WordBack, "Move back a word"),
+                    Binding::new(&["Alt-f"], WordForward, "Move forward a word"),
+                    Binding::new(
+                        &["Ctrl-w", "Alt-Backspace"],
+                        DeleteWordBack,
+                        "Delete word before cursor",
+                    ),
+                    Binding::new(&["Alt-d"], DeleteWordForward, "Delete word after cursor"),
+                    Binding::new(&["Ctrl-u"], DeleteToLineStart, "Delete to line start"),
+                    Binding::new(&["Ctrl-k"], DeleteToLineEnd, "Delete to line end"),
+                    Binding::new(&["F1"], Help, "Help").hint(),
+                    Binding::new(&["Esc", "Ctrl-c", "Ctrl-g"], Quit, "Quit").hint(),
+                ]);
+                (list, Vec::new())
+            }
+        };
+
+        // The tree takes the list's keys, but the arrows open and close categories.
+        let mut tree = vec![
+            Binding::new(&["Right"], Expand, "Expand category").hint(),
+            Binding::new(&["Left"], Collapse, "Collapse category").hint(),
+            Binding::new(&["F2"], RenameCategory, "Rename category"),
+        ];
+        for binding in &list {
+            let mut binding = binding.clone();
+            binding
+                .keys
+                .retain(|seq| !tree.iter().any(|b: &Binding| b.keys.contains(seq)));
+            if !binding.keys.is_empty() {
+                tree.push(binding);
+            }
+        }
+
+        Self {
+            list,
+            normal,
+            tree,
+            editor: vec![
+                Binding::new(&["Ctrl-s"], Save, "Save").hint(),
+                Binding::new(&["Alt-e"], ExternalEdit, "Edit in $EDITOR").hint(),
+                Binding::new(&["Ctrl-c"], Cancel, "Cancel").hint(),
+                Binding::new(&["F1"], Help, "Help").hint(),
+            ],
+            prompt: vec![
+                Binding::new(&["Enter"], Submit, "Confirm").hint(),
+                Binding::new(&["Esc", "Ctrl-c"], Cancel, "Cancel").hint(),
+            ],
+            fill: vec![
+                Binding::new(&["Enter"], Submit, "Confirm").hint(),
+                Binding::new(&["Down", "Ctrl-n"], SelectNext, "Next choice").hint(),
+                Binding::new(
+                    &["Up", "Ctrl-p"],
+                    SelectPrev,
+                    "Previous choice, or typed text",
+                )
+                .hint(),
+                Binding::new(&["Esc", "Ctrl-c"], Cancel, "Cancel").hint(),
+                Binding::new(&["F1"], Help, "Help"),
+            ],
+            dedupe: vec![
+                Binding::new(&["Enter", "m"], Merge, "Merge group").hint(),
+                Binding::new(&["Down", "j"], SelectNext, "Next group").hint(),
+                Binding::new(&["Up", "k"], SelectPrev, "Previous group").hint(),
+                Binding::new(&["Esc", "q"], Cancel, "Close").hint(),
+                Binding::new(&["F1", "?"], Help, "Help"),
+            ],
+            jobs: vec![
+                Binding::new(&["Down", "j"], SelectNext, "Next job").hint(),
+                Binding::new(&["Up", "k"], SelectPrev, "Previous job").hint(),
+                Binding::new(&["i"], Interrupt, "Send SIGINT").hint(),
+                Binding::new(&["t"], Terminate, "Send SIGTERM").hint(),
+                Binding::new(&["r"], Restart, "Restart").hint(),
+                Binding::new(&["Shift-Up", "Ctrl-y"], ScrollUp, "Scroll output up"),
+                Binding::new(&["Shift-Down", "Ctrl-e"], ScrollDown, "Scroll output down"),
+                Binding::new(&["PageUp"], ScrollPageUp, "Page output up"),
+                Binding::new(&["PageDown"], ScrollPageDown, "Page output down"),
+                Binding::new(&["Esc", "q"], Cancel, "Close").hint(),
+                Binding::new(&["F1", "?"], Help, "Help"),
+            ],
+            help: vec![
+                Binding::new(&["Down", "j"], ScrollDown, "Scroll down"),
+                Binding::new(&["Up", "k"], ScrollUp, "Scroll up"),
+                Binding::new(&["Esc", "F1", "?", "q"], Cancel, "Close help").hint(),
+            ],
+        }
+    }
+
+    pub fn bindings(&self, mode: Mode) -> &[Binding] {
+        match mode {
+            Mode::List => &self.list,
+            Mode::Normal => &self.normal,
+            Mode::Tree => &self.tree,
+            Mode::Editor => &self.editor,
+            Mode::Prompt => &self.prompt,
+            Mode::Fill => &self.fill,
+            Mode::Dedupe => &self.dedupe,
+            Mode::Jobs => &self.jobs,
+            Mode::Help => &self.help,
+        }
+    }
+
+    /// Looks up the sequence of keys pressed so far.
+    pub fn lookup(&self, mode: Mode, keys: &[Key]) -> Lookup {
+        let mut pending = false;
+        for binding in self.bindings(mode) {
+            for seq in &binding.keys {
+                if seq.as_slice() == keys {
+                    return Lookup::Action(binding.action);
+                }
+                pending |= seq.starts_with(keys);
+            }
+        }
+        if pending {
+            Lookup::Pending
+        } else {
+            Lookup::Unbound
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(preset: KeymapPreset, mode: Mode, keys: &str) -> Lookup {
+        let keys: Vec<Key> = keys.split(' ').map(Key::parse).collect();
+        Keymap::preset(preset).lookup(mode, &keys)
+    }
+
+    #[test]
+    fn looks_up_default_keys() {
+        let lookup = |keys| lookup(KeymapPreset::Default, Mode::List, keys);
+        assert_eq!(lookup("Ctrl-r"), Lookup::Action(Action::Remove));
+        assert_eq!(lookup("Ctrl-R"), Lookup::Action(Action::Remove));
+        assert_eq!(lookup("Ctrl-End"), Lookup::Action(Action::SelectLast));
+        assert_eq!(lookup("?"), Lookup::Unbound);
+        assert_eq!(lookup("End"), Lookup::Unbound);
+    }
+
+    #[test]
+    fn looks_up_vim_sequences() {
+        let normal = |keys| lookup(KeymapPreset::Vim, Mode::Normal, keys);
+        assert_eq!(normal("g"), Lookup::Pending);
+        assert_eq!(normal("g g"), Lookup::Action(Action::SelectFirst));
+        assert_eq!(normal("d"), Lookup::Pending);
+        assert_eq!(normal("d d"), Lookup::Action(Action::Remove));
+        assert_eq!(normal("y y"), Lookup::Action(Action::Copy));
+        assert_eq!(normal("T s"), Lookup::Action(Action::RunInSplit));
+        assert_eq!(normal("G"), Lookup::Action(Action::SelectLast));
+        assert_eq!(normal("g d"), Lookup::Unbound);
+        assert_eq!(normal("x"), Lookup::Unbound);
+        // Insert mode types plain chars.
+        let insert = |keys| lookup(KeymapPreset::Vim, Mode::List, keys);
+        assert_eq!(insert("g"), Lookup::Unbound);
+        assert_eq!(insert("Esc"), Lookup::Action(Action::NormalMode));
+    }
+
+    #[test]
+    fn looks_up_the_readline_leader() {
+        for mode in [Mode::List, Mode::Tree] {
+            let lookup = |keys| lookup(KeymapPreset::Readline, mode, keys);
+            assert_eq!(lookup("Ctrl-x"), Lookup::Pending);
+            assert_eq!(lookup("Ctrl-x t"), Lookup::Action(Action::AddTag));
+            assert_eq!(lookup("Ctrl-x T"), Lookup::Pending);
+            assert_eq!(lookup("Ctrl-x T w"), Lookup::Action(Action::RunInWindow));
+            assert_eq!(
+                lookup("Ctrl-x Ctrl-e"),
+                Lookup::Action(Action::ExternalEdit)
+            );
+            assert_eq!(lookup("Ctrl-x z"), Lookup::Unbound);
+            assert_eq!(lookup("Ctrl-r"), Lookup::Action(Action::SelectNext));
+            assert_eq!(lookup("Ctrl-a"), Lookup::Action(Action::LineStart));
+            assert_eq!(lookup("t"), Lookup::Unbound);
+        }
+    }
+
+    #[test]
+    fn every_sequence_is_reachable() {
+        let presets = [
+            KeymapPreset::Default,
+            KeymapPreset::Vim,
+            KeymapPreset::Readline,
+        ];
+        let modes = [
+            Mode::List,
+            Mode::Normal,
+            Mode::Tree,
+            Mode::Editor,
+            Mode::Prompt,
+            Mode::Fill,
+            Mode::Dedupe,
+            Mode::Jobs,
+            Mode::Help,
+        ];
+        for preset in presets {
+            let keymap = Keymap::preset(preset);
+            for mode in modes {
+                let seqs: Vec<&Vec<Key>> = keymap
+                    .bindings(mode)
+                    .iter()
+                    .flat_map(|binding| &binding.keys)
+                    .collect();
+                for (i, a) in seqs.iter().enumerate() {
+                    for b in &seqs[i + 1..] {
+                        assert!(
+                            !a.starts_with(b) && !b.starts_with(a),
+                            "{preset:?} {mode}: {a:?} and {b:?} clash"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,12 +1,34 @@
+mod clipboard;
+mod dedupe;
+mod external;
+mod highlight;
+mod jobs;
+mod keymap;
 pub mod model;
+mod placeholder;
+mod reference;
+mod runbook;
+mod search;
+mod syntax;
+mod tmux;
+mod tree;
 mod update;
 mod view;
 
-use std::io::stdout;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, stdout, BufRead, Write},
+    path::Path,
+};
 
 use anyhow::Result;
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    event::{
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture,
+    },
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
@@ -15,17 +37,31 @@ use ratatui::{
     Terminal,
 };
 
+pub use external::edit_all;
+
 pub fn init_terminal() -> Result<Terminal<impl Backend>> {
+    setup_terminal()?;
+    let terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    Ok(terminal)
+}
+
+/// Puts the terminal in the state the TUI runs in, also when taking it back
+/// after another program used it.
+fn setup_terminal() -> Result<()> {
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
     stdout().execute(EnableMouseCapture)?;
-    let terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-    Ok(terminal)
+    // Pastes arrive as one event instead of a key press per char.
+    stdout().execute(EnableBracketedPaste)?;
+    stdout().execute(EnableFocusChange)?;
+    Ok(())
 }
 
 pub fn restore_terminal() -> Result<()> {
     stdout().execute(LeaveAlternateScreen)?;
     stdout().execute(DisableMouseCapture)?;
+    stdout().execute(DisableBracketedPaste)?;
+    stdout().execute(DisableFocusChange)?;
     disable_raw_mode()?;
     Ok(())
 }
@@ -35,7 +71,58 @@ pub fn install_panic_hook() {
     std::panic::set_hook(Box::new(move |panic_info| {
         stdout().execute(LeaveAlternateScreen).unwrap();
         stdout().execute(DisableMouseCapture).unwrap();
+        stdout().execute(DisableBracketedPaste).unwrap();
+        stdout().execute(DisableFocusChange).unwrap();
         disable_raw_mode().unwrap();
         original_hook(panic_info);
     }));
 }
+
+/// Creates a file only the user can read and write. Fails if `path` exists,
+/// so no one else can have prepared it in the shared temp directory.
+fn create_private(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path)
+}
+
+/// Asks `question` in the restored terminal until the answer is one of `choices`.
+/// An empty answer picks the first choice, and `closed` is the answer if stdin is closed.
+fn ask<T: Copy>(question: &str, choices: &[(&str, T)], closed: T) -> Result<T> {
+    ask_with(
+        &mut io::stdin().lock(),
+        &mut stdout(),
+        question,
+        choices,
+        closed,
+    )
+}
+
+/// `ask`, reading the answers from `input` and writing the question to `output`.
+fn ask_with<T: Copy>(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    choices: &[(&str, T)],
+    closed: T,
+) -> Result<T> {
+    loop {
+        write!(output, "{question} ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(closed);
+        }
+        let line = line.trim().to_lowercase();
+        let answer = if line.is_empty() {
+            choices.first()
+        } else {
+            choices.iter().find(|(key, _)| line.starts_with(key))
+        };
+        if let Some((_, answer)) = answer {
+            return Ok(*answer);
+        }
+    }
+}
@@ -1,19 +1,197 @@
-use std::{collections::HashMap, fmt::Display, fs, path::PathBuf};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::Result;
-use ratatui::widgets::ListState;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    widgets::ListState,
+};
 use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
 use tui_textarea::TextArea;
 
-use crate::event::EventHandler;
+use crate::{
+    config::{Config, KeymapPreset, OnQuit},
+    event::EventHandler,
+};
+
+use super::{
+    jobs::{Job, Signal},
+    keymap::{Action, Key, Keymap, Mode},
+    placeholder::{self, Generated},
+    reference,
+    search::{SearchMode, SortOrder, QUALIFIER_PATTERN},
+    syntax::Report,
+    tmux::Target,
+    tree::Row,
+};
 #[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug, Default)]
 pub struct Snippet {
+    /// Stays the same as the store changes, for `{{ref:<id>}}`. Kept out of the
+    /// editor, chopsticks assigns it.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub id: u64,
     #[serde(default)]
     pub priority: i64,
+    /// Empty for runbooks, whose commands are in `steps`.
+    #[serde(default)]
+    pub cmd: String,
+    pub description: String,
+    /// Interpreter used to run the snippet, e.g. `bash` or `python3`. Also picks the highlighter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Directory the snippet is about, searchable with `dir:`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// Path in the category tree, like `k8s/debug/network`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Name other snippets use to include this one's command with `{{ref:<alias>}}`,
+    /// besides its id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// The tmux pane executing the snippet runs it in, when inside tmux.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<Target>,
+    /// Makes the snippet a runbook: its steps run one after another, with a
+    /// confirmation before each.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<Step>,
+    /// How many times the snippet was executed or copied.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub use_count: u64,
+    /// Seconds since the Unix epoch. Kept out of the editor, chopsticks maintains them.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub updated_at: u64,
+    /// When the snippet was last executed or copied, if ever.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<u64>,
+    /// Exit status of the last execution, or of the last step run for a runbook.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_exit: Option<i32>,
+    /// Definitions of the `<name>` placeholders in the command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub placeholders: Vec<Placeholder>,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+/// The snippets in the content of the store file.
+pub(super) fn parse_store(content: &str) -> Result<Vec<Snippet>> {
+    let mut toml = toml::from_str::<HashMap<String, Vec<Snippet>>>(content)?;
+    Ok(toml.remove("snippets").unwrap_or_default())
+}
+
+/// Reads the store at `path`, filling in the timestamps and ids it lacks.
+fn read_store(path: &Path) -> Result<Vec<Snippet>> {
+    let mut snippets = parse_store(&fs::read_to_string(path)?)?;
+
+    // Snippets saved before timestamps existed date from the last change to the file.
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or_else(now, |since| since.as_secs());
+    for snippet in &mut snippets {
+        if snippet.created_at == 0 {
+            snippet.created_at = modified;
+        }
+        if snippet.updated_at == 0 {
+            snippet.updated_at = snippet.created_at;
+        }
+    }
+    assign_ids(&mut snippets);
+
+    Ok(snippets)
+}
+
+/// Gives the snippets without an id, or with the id of an earlier one, a new id.
+pub(super) fn assign_ids(snippets: &mut [Snippet]) {
+    let mut next = next_id(snippets);
+    let mut seen = HashSet::new();
+    for snippet in snippets {
+        if snippet.id == 0 || !seen.insert(snippet.id) {
+            snippet.id = next;
+            next += 1;
+        }
+    }
+}
+
+/// The id for a new snippet.
+pub(super) fn next_id(snippets: &[Snippet]) -> u64 {
+    snippets.iter().map(|s| s.id).max().unwrap_or_default() + 1
+}
+
+/// Replaces the content of the store file with `snippets`.
+pub(super) fn write_store(snippets: &[Snippet]) -> Result<()> {
+    let content = toml::to_string_pretty(&HashMap::from([("snippets", snippets)]))?;
+    fs::write(App::snippet_path(), content)?;
+    Ok(())
+}
+
+/// The current time, in seconds since the Unix epoch.
+pub(super) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// One command of a runbook.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug, Default)]
+pub struct Step {
     pub cmd: String,
+    #[serde(default)]
     pub description: String,
 }
 
+/// How to fill in a `<name>` placeholder.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Debug, Default)]
+pub struct Placeholder {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// Command whose output lines are offered as values.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generator: Option<String>,
+}
+
+impl Snippet {
+    pub fn is_runbook(&self) -> bool {
+        !self.steps.is_empty()
+    }
+
+    /// Counts a use, at `now`.
+    pub(super) fn mark_used(&mut self, now: u64) {
+        self.use_count += 1;
+        self.last_used_at = Some(now);
+    }
+
+    /// The command, or for a runbook the commands of its steps, one per line.
+    pub fn script(&self) -> Cow<'_, str> {
+        if self.is_runbook() {
+            let cmds: Vec<&str> = self.steps.iter().map(|step| step.cmd.as_str()).collect();
+            Cow::Owned(cmds.join("\n"))
+        } else {
+            Cow::Borrowed(&self.cmd)
+        }
+    }
+}
+
 impl Display for Snippet {
     #[rustfmt::skip]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -23,21 +201,230 @@ impl Display for Snippet {
             self.priority,
             if self.cmd.is_empty() { "\n" } else { self.cmd.as_str() },
             if self.description.is_empty() { "\n" } else { self.description.as_str() },
-        )
+        )?;
+        if let Some(shell) = &self.shell {
+            write!(f, "\nshell = {}", toml::Value::from(shell.as_str()))?;
+        }
+        if !self.tags.is_empty() {
+            write!(f, "\ntags = {}", toml::Value::from(self.tags.clone()))?;
+        }
+        if let Some(dir) = &self.dir {
+            write!(f, "\ndir = {}", toml::Value::from(dir.as_str()))?;
+        }
+        if let Some(collection) = &self.collection {
+            write!(f, "\ncollection = {}", toml::Value::from(collection.as_str()))?;
+        }
+        if let Some(category) = &self.category {
+            write!(f, "\ncategory = {}", toml::Value::from(category.as_str()))?;
+        }
+        if let Some(alias) = &self.alias {
+            write!(f, "\nalias = {}", toml::Value::from(alias.as_str()))?;
+        }
+        if let Some(target) = self.target {
+            write!(f, "\ntarget = \"{target}\"")?;
+        }
+        if self.use_count > 0 {
+            write!(f, "\nuse_count = {}", self.use_count)?;
+        }
+        for step in &self.steps {
+            write!(
+                f,
+                "\n\n[[steps]]\ncmd = \'\'\'{}\'\'\'\ndescription = {}",
+                step.cmd,
+                toml::Value::from(step.description.as_str()),
+            )?;
+        }
+        for placeholder in &self.placeholders {
+            write!(
+                f,
+                "\n\n[[placeholders]]\nname = {}",
+                toml::Value::from(placeholder.name.as_str())
+            )?;
+            if let Some(default) = &placeholder.default {
+                write!(f, "\ndefault = {}", toml::Value::from(default.as_str()))?;
+            }
+            if let Some(generator) = &placeholder.generator {
+                write!(f, "\ngenerator = \'\'\'{}\'\'\'", generator)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A snippet as listed in the search results.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Hit {
+    /// Position of the snippet in `App::snippets`.
+    pub index: usize,
+    pub score: i64,
+    /// Char positions matched by the query, per field.
+    pub cmd: Vec<usize>,
+    pub description: Vec<usize>,
+    /// Positions in the tags joined by spaces.
+    pub tags: Vec<usize>,
+}
+
+/// What a submitted prompt does with its input.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PromptAction {
+    AddTag,
+    RemoveTag,
+    MoveToCollection,
+    MoveToCategory,
+    /// Renames the selected category of the tree, and everything below it.
+    RenameCategory,
+    Export,
+    /// Asks before removing more than one snippet.
+    ConfirmRemove,
+}
+
+/// A one-line input popup, used by actions that need an argument.
+#[derive(Debug)]
+pub(super) struct Prompt<'a> {
+    pub action: PromptAction,
+    pub input: TextArea<'a>,
+}
+
+/// What happens with a command once its placeholders are filled in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FillAction {
+    Execute,
+    Copy,
+    /// Run as a job, in the background.
+    Background,
+    /// Run in a tmux pane.
+    Tmux(Target),
+}
+
+/// Asks for the values of a snippet's placeholders, one at a time.
+#[derive(Debug)]
+pub(super) struct Fill<'a> {
+    pub action: FillAction,
+    /// Position of the snippet in `App::snippets`.
+    pub index: usize,
+    /// The snippet with its references expanded.
+    pub snippet: Snippet,
+    pub names: Vec<String>,
+    /// Values of the placeholders before `current`.
+    pub values: HashMap<String, String>,
+    pub current: usize,
+    pub input: TextArea<'a>,
+    /// Suggested values, from the placeholder's generator.
+    pub choices: Vec<String>,
+    /// Choices matching the input, with the matched char positions.
+    pub matches: Vec<(usize, Vec<usize>)>,
+    /// The selected match. With none selected, the typed text is used.
+    pub state: ListState,
+    /// The running generator.
+    pub generator: Option<oneshot::Receiver<Generated>>,
+    pub generator_error: Option<String>,
+}
+
+impl<'a> Fill<'a> {
+    /// The value of the current placeholder: the selected choice, or else the typed text.
+    pub fn value(&self) -> String {
+        match self.state.selected().and_then(|i| self.matches.get(i)) {
+            Some((choice, _)) => self.choices[*choice].clone(),
+            None => self.input.lines()[0].clone(),
+        }
+    }
+
+    /// The command as it will run with the values so far, and the char
+    /// positions of the filled-in values.
+    pub fn preview(&self) -> (String, Vec<usize>) {
+        let mut values = self.values.clone();
+        values.insert(self.names[self.current].clone(), self.value());
+        let (filled, positions) = placeholder::fill_positions(&self.snippet, &values);
+        (filled.script().into_owned(), positions)
     }
 }
 
+/// Groups of near-identical snippets, to merge.
+#[derive(Debug, Default)]
+pub(super) struct Dedupe {
+    /// Indices into `App::snippets`.
+    pub groups: Vec<Vec<usize>>,
+    pub state: ListState,
+}
+
+/// The panel listing the jobs, with the output of the selected one.
+#[derive(Debug, Default)]
+pub(super) struct JobsPanel {
+    pub state: ListState,
+    /// The first line of output shown.
+    pub scroll: u16,
+    /// Keep the last lines of output in view as more arrive.
+    pub follow: bool,
+}
+
+/// The category tree shown in place of the flat list.
+#[derive(Debug, Default)]
+pub(super) struct Tree {
+    /// Paths of the expanded categories.
+    pub expanded: HashSet<String>,
+    /// The category the tree and the search are limited to.
+    pub scope: Option<String>,
+    pub rows: Vec<Row>,
+    pub state: ListState,
+}
+
+/// Screen areas of the last render, used to map mouse events to widgets.
+#[derive(Debug, Default)]
+pub(super) struct Areas {
+    pub search_bar: Rect,
+    /// Inner area of the list, one row per hit.
+    pub list: Rect,
+    pub details: Rect,
+    /// Clickable hints of the bottom bar, and the action each one stands for.
+    pub buttons: Vec<(Rect, Action)>,
+}
+
 #[derive(Debug)]
 pub struct App<'a> {
     pub quit: bool,
     pub terminal_restored: bool,
+    /// The terminal was used by another program, so the next frame is drawn from scratch.
+    pub clear_screen: bool,
     pub(super) is_editing: bool,
     pub(super) error_msg: Option<String>,
+    pub(super) status_msg: Option<String>,
     pub(super) search_bar: TextArea<'a>,
+    pub(super) search_mode: SearchMode,
+    pub(super) sort_order: SortOrder,
     pub(super) editor: Option<TextArea<'a>>,
+    /// The snippet being edited, or `None` when adding one.
+    pub(super) editing: Option<usize>,
+    /// First editor line in view, following the editor's own scrolling.
+    pub(super) editor_top: u16,
+    /// Problems the syntax check found in the editor content on save.
+    pub(super) syntax_report: Option<Report>,
     pub(super) snippets: Vec<Snippet>,
+    pub(super) hits: Vec<Hit>,
+    /// Indices into `snippets` marked for bulk actions.
+    pub(super) marked: BTreeSet<usize>,
+    pub(super) prompt: Option<Prompt<'a>>,
+    pub(super) fill: Option<Fill<'a>>,
+    pub(super) dedupe: Option<Dedupe>,
+    pub(super) jobs: Vec<Job>,
+    pub(super) jobs_panel: Option<JobsPanel>,
+    pub(super) tree: Option<Tree>,
+    /// Whether the preview pane with the details of the selected snippet is shown.
+    pub(super) show_preview: bool,
+    /// The mode whose keys the help overlay lists, while it is open.
+    pub(super) help: Option<Mode>,
+    pub(super) help_scroll: u16,
+    pub(super) keymap: Keymap,
+    /// Keys of a sequence typed so far, like the first `g` of `gg`.
+    pub(super) pending_keys: Vec<Key>,
+    /// Whether the list is in vim normal mode rather than typing into the search bar.
+    pub(super) normal_mode: bool,
     pub(super) state: ListState,
+    pub(super) details_scroll: u16,
+    pub(super) areas: Areas,
+    /// Time and list row of the last click, to detect double-clicks.
+    pub(super) last_click: Option<(Instant, usize)>,
     pub(super) events: EventHandler,
+    pub(super) config: Config,
 }
 
 impl<'a> Default for App<'a> {
@@ -51,52 +438,224 @@ impl<'a> App<'a> {
         Self {
             quit: false,
             terminal_restored: false,
+            clear_screen: false,
             is_editing: false,
             error_msg: None,
+            status_msg: None,
             search_bar: TextArea::default(),
+            search_mode: SearchMode::default(),
+            sort_order: SortOrder::default(),
             editor: None,
+            editing: None,
+            editor_top: 0,
+            syntax_report: None,
             snippets: Vec::new(),
+            hits: Vec::new(),
+            marked: BTreeSet::new(),
+            prompt: None,
+            fill: None,
+            dedupe: None,
+            jobs: Vec::new(),
+            jobs_panel: None,
+            tree: None,
+            show_preview: true,
+            help: None,
+            help_scroll: 0,
+            keymap: Keymap::default(),
+            pending_keys: Vec::new(),
+            normal_mode: false,
             state: ListState::default(),
+            details_scroll: 0,
+            areas: Areas::default(),
+            last_click: None,
             events: EventHandler::new(16),
+            config: Config::default(),
         }
     }
 
     pub fn init(&mut self) {
+        self.config = Config::load().expect("Failed to load config file");
+        self.keymap = Keymap::preset(self.config.keymap);
+        self.normal_mode = self.config.keymap == KeymapPreset::Vim;
+        // Highlights the field qualifiers typed into the search bar.
+        self.search_bar
+            .set_search_pattern(QUALIFIER_PATTERN)
+            .expect("Invalid qualifier pattern");
+        self.search_bar.set_search_style(
+            Style::new()
+                .fg(self.config.theme.qualifier)
+                .add_modifier(Modifier::BOLD),
+        );
         self.snippets = self.load_snippets().expect("Failed to load snippets file");
-        self.state.select(Some(0));
+        self.search_snippet().expect("Failed to list snippets");
+    }
+
+    /// The mode that receives key presses.
+    pub(super) fn mode(&self) -> Mode {
+        if self.help.is_some() {
+            Mode::Help
+        } else if self.prompt.is_some() {
+            Mode::Prompt
+        } else if self.fill.is_some() {
+            Mode::Fill
+        } else if self.dedupe.is_some() {
+            Mode::Dedupe
+        } else if self.jobs_panel.is_some() {
+            Mode::Jobs
+        } else if self.is_editing {
+            Mode::Editor
+        } else if self.normal_mode {
+            Mode::Normal
+        } else if self.tree.is_some() {
+            Mode::Tree
+        } else {
+            Mode::List
+        }
+    }
+
+    /// The hit under the cursor, if the list isn't empty.
+    pub(super) fn selected_hit(&self) -> Option<&Hit> {
+        self.hits.get(self.state.selected()?)
+    }
+
+    /// The category selected in the tree, if a category row is selected.
+    pub(super) fn selected_category(&self) -> Option<&str> {
+        let tree = self.tree.as_ref()?;
+        match tree.rows.get(tree.state.selected()?)? {
+            Row::Category { path, .. } => Some(path),
+            Row::Snippet { .. } => None,
+        }
+    }
+
+    pub(super) fn selected_snippet(&self) -> Option<&Snippet> {
+        self.snippets.get(self.selected_hit()?.index)
+    }
+
+    /// The command of the snippet at `index`, with its `{{ref:<alias>}}`s expanded.
+    pub(super) fn expanded_script(&self, index: usize) -> Result<String> {
+        let snippet = &self.snippets[index];
+        reference::expand(&self.snippets, snippet, &snippet.script())
+    }
+
+    /// A copy of the snippet at `index` whose commands have their references expanded.
+    pub(super) fn expanded_snippet(&self, index: usize) -> Result<Snippet> {
+        let snippet = &self.snippets[index];
+        let mut expanded = snippet.clone();
+        expanded.cmd = reference::expand(&self.snippets, snippet, &snippet.cmd)?;
+        for step in &mut expanded.steps {
+            step.cmd = reference::expand(&self.snippets, snippet, &step.cmd)?;
+        }
+        Ok(expanded)
+    }
+
+    /// Indices of the snippets an action applies to: the marked ones, or else
+    /// the selected one.
+    pub(super) fn targets(&self) -> Vec<usize> {
+        if self.marked.is_empty() {
+            self.selected_hit()
+                .map(|hit| hit.index)
+                .into_iter()
+                .collect()
+        } else {
+            self.marked.iter().copied().collect()
+        }
+    }
+
+    /// Updates the snippet indices of the jobs after the snippets at the sorted
+    /// indices `removed` were removed.
+    pub(super) fn forget_snippets(&mut self, removed: &[usize]) {
+        for job in &mut self.jobs {
+            job.index = job
+                .index
+                .and_then(|index| match removed.binary_search(&index) {
+                    Ok(_) => None,
+                    Err(before) => Some(index - before),
+                });
+        }
+    }
+
+    /// How many marked snippets the current search hides.
+    pub(super) fn hidden_marks(&self) -> usize {
+        let shown: HashSet<usize> = self.hits.iter().map(|hit| hit.index).collect();
+        self.marked.iter().filter(|i| !shown.contains(i)).count()
     }
 
     pub fn quit(&mut self) -> Result<()> {
-        let snippets =
-            toml::to_string_pretty(&HashMap::from([("snippets", &self.snippets)])).unwrap();
-        let snippet_path = Self::snippet_path();
-        fs::write(snippet_path, snippets).unwrap();
+        write_store(&self.snippets)?;
+        for job in &self.jobs {
+            job.remove_script();
+            if !job.is_running() {
+                job.remove_log();
+            } else if self.config.jobs.on_quit == OnQuit::Kill {
+                let _ = job.signal(Signal::Terminate);
+                job.remove_log();
+            }
+        }
         self.quit = true;
         Ok(())
     }
 
     fn load_snippets(&mut self) -> Result<Vec<Snippet>> {
         let snippet_path = Self::snippet_path();
-        let content = if snippet_path.exists() {
-            fs::read_to_string(snippet_path)?
-        } else {
+        if !snippet_path.exists() {
             fs::create_dir_all(snippet_path.parent().unwrap())?;
-            fs::File::create(snippet_path)?;
-            String::new()
-        };
-
-        let mut toml = toml::from_str::<HashMap<String, Vec<Snippet>>>(&content)?;
-
-        Ok(toml
-            .remove("snippets")
-            .get_or_insert_with(Vec::new)
-            .to_owned())
+            fs::File::create(&snippet_path)?;
+        }
+        read_store(&snippet_path)
     }
 
-    fn snippet_path() -> PathBuf {
+    pub(super) fn snippet_path() -> PathBuf {
         dirs::data_local_dir()
             .unwrap()
             .join("chopsticks")
             .join("snippets.toml")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assigns_missing_and_duplicate_ids() {
+        let mut snippets: Vec<Snippet> = [0, 4, 0, 4, 2]
+            .into_iter()
+            .map(|id| Snippet {
+                id,
+                ..Default::default()
+            })
+            .collect();
+        assign_ids(&mut snippets);
+        let ids: Vec<u64> = snippets.iter().map(|s| s.id).collect();
+        assert_eq!(ids, [5, 4, 6, 7, 2]);
+        assert_eq!(next_id(&snippets), 8);
+        assert_eq!(next_id(&[]), 1);
+    }
+
+    #[test]
+    fn dates_old_snippets_from_the_file() {
+        let path =
+            std::env::temp_dir().join(format!("chopsticks-store-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "[[snippets]]\ncmd = 'ls'\ndescription = ''\n\n\
+             [[snippets]]\ncmd = 'pwd'\ndescription = ''\ncreated_at = 5\n",
+        )
+        .unwrap();
+        let modified = UNIX_EPOCH + std::time::Duration::from_secs(1000);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(modified))
+            .unwrap();
+
+        let snippets = read_store(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let times: Vec<(u64, u64)> = snippets
+            .iter()
+            .map(|s| (s.created_at, s.updated_at))
+            .collect();
+        assert_eq!(times, [(1000, 1000), (5, 5)]);
+        assert_eq!(snippets[1].id, 2);
+    }
+}
@@ -0,0 +1,242 @@
+use std::{borrow::Cow, collections::HashMap, sync::OnceLock};
+
+use regex::Regex;
+use tokio::{process::Command, sync::oneshot};
+
+use super::{highlight::is_shell, model::Snippet};
+
+/// Lines printed by a generator command, or why it failed.
+pub(super) type Generated = Result<Vec<String>, String>;
+
+/// Matches `<name>`, capturing the name.
+fn pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"<([A-Za-z_][A-Za-z0-9_-]*)>").unwrap())
+}
+
+/// Names of the placeholders in the commands of `snippet`, in order of first appearance.
+pub(super) fn names(snippet: &Snippet) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for caps in pattern().captures_iter(&snippet.script()) {
+        if !names.iter().any(|name| name == &caps[1]) {
+            names.push(caps[1].to_string());
+        }
+    }
+    names
+}
+
+/// Single-quotes `value` for the shell, unless it only has chars that need no quoting.
+pub(super) fn quote(value: &str) -> Cow<'_, str> {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        Cow::Borrowed(value)
+    } else {
+        Cow::Owned(format!("'{}'", value.replace('\'', r"'\''")))
+    }
+}
+
+/// The shell quotes a part of a command is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quotes {
+    None,
+    Single,
+    Double,
+}
+
+impl Quotes {
+    /// The quotes in effect after `text`, which starts inside `self`.
+    fn after(self, text: &str) -> Self {
+        let mut quotes = self;
+        let mut escaped = false;
+        for c in text.chars() {
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            quotes = match (quotes, c) {
+                (Quotes::None | Quotes::Double, '\\') => {
+                    escaped = true;
+                    quotes
+                }
+                (Quotes::None, '\'') => Quotes::Single,
+                (Quotes::None, '"') => Quotes::Double,
+                (Quotes::Single, '\'') | (Quotes::Double, '"') => Quotes::None,
+                _ => quotes,
+            };
+        }
+        quotes
+    }
+
+    /// `value` made literal at a position inside these quotes.
+    fn escape(self, value: &str) -> Cow<'_, str> {
+        match self {
+            Quotes::None => quote(value),
+            Quotes::Single if value.contains('\'') => Cow::Owned(value.replace('\'', r"'\''")),
+            Quotes::Double if value.contains(['"', '\\', '$', '`']) => {
+                let mut escaped = String::with_capacity(value.len() + 1);
+                for c in value.chars() {
+                    if matches!(c, '"' | '\\' | '$' | '`') {
+                        escaped.push('\\');
+                    }
+                    escaped.push(c);
+                }
+                Cow::Owned(escaped)
+            }
+            Quotes::Single | Quotes::Double => Cow::Borrowed(value),
+        }
+    }
+}
+
+/// Replaces the placeholders in `text` that have a value. If `quoted`, values
+/// are made literal for the shell: escaped for the quotes a placeholder is in,
+/// or else quoted. Returns the result and the char positions of the inserted values.
+pub(super) fn substitute(
+    text: &str,
+    values: &HashMap<String, String>,
+    quoted: bool,
+) -> (String, Vec<usize>) {
+    let mut result = String::with_capacity(text.len());
+    let mut positions = Vec::new();
+    let mut last = 0;
+    let mut quotes = Quotes::None;
+    for caps in pattern().captures_iter(text) {
+        let Some(value) = values.get(&caps[1]) else {
+            continue;
+        };
+        let whole = caps.get(0).unwrap();
+        let before = &text[last..whole.start()];
+        quotes = quotes.after(before);
+        result.push_str(before);
+        let value = if quoted {
+            quotes.escape(value)
+        } else {
+            Cow::Borrowed(value.as_str())
+        };
+        let start = result.chars().count();
+        positions.extend(start..start + value.chars().count());
+        result.push_str(&value);
+        last = whole.end();
+    }
+    result.push_str(&text[last..]);
+    (result, positions)
+}
+
+/// A copy of `snippet` with the placeholders in its commands filled in.
+/// Values are quoted unless the snippet runs in another interpreter than a shell.
+pub(super) fn fill(snippet: &Snippet, values: &HashMap<String, String>) -> Snippet {
+    fill_positions(snippet, values).0
+}
+
+/// Like `fill`, also returning the char positions of the inserted values in
+/// the script of the filled-in snippet.
+pub(super) fn fill_positions(
+    snippet: &Snippet,
+    values: &HashMap<String, String>,
+) -> (Snippet, Vec<usize>) {
+    let quoted = is_shell(snippet.shell.as_deref());
+    let mut filled = snippet.clone();
+    if !filled.is_runbook() {
+        let (cmd, positions) = substitute(&snippet.cmd, values, quoted);
+        filled.cmd = cmd;
+        return (filled, positions);
+    }
+
+    // Each step is a command of its own, quotes don't carry over to the next.
+    filled.cmd = substitute(&snippet.cmd, values, quoted).0;
+    let mut positions = Vec::new();
+    let mut offset = 0;
+    for step in &mut filled.steps {
+        let (cmd, step_positions) = substitute(&step.cmd, values, quoted);
+        positions.extend(step_positions.into_iter().map(|p| p + offset));
+        // The steps are joined by newlines.
+        offset += cmd.chars().count() + 1;
+        step.cmd = cmd;
+    }
+    (filled, positions)
+}
+
+/// Runs a generator command in the background, like the snippet itself would
+/// run: with its shell.
+pub(super) fn generate(snippet: &Snippet, cmd: &str) -> oneshot::Receiver<Generated> {
+    let (sender, receiver) = oneshot::channel();
+    let mut command = Command::new(snippet.shell.as_deref().unwrap_or("sh"));
+    command.arg("-c").arg(cmd).kill_on_drop(true);
+
+    tokio::spawn(async move {
+        let generated = match command.output().await {
+            Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(String::from)
+                .collect()),
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(match stderr.lines().last() {
+                    Some(line) => format!("Generator failed: {line}"),
+                    None => format!("Generator failed with {}", output.status),
+                })
+            }
+            Err(err) => Err(format!("Failed to run generator: {err}")),
+        };
+        // The prompt may be gone by now, then nobody wants the result.
+        let _ = sender.send(generated);
+    });
+
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn substituted(text: &str, value: &str) -> String {
+        let values = HashMap::from([("msg".to_string(), value.to_string())]);
+        substitute(text, &values, true).0
+    }
+
+    #[test]
+    fn quotes_values_outside_quotes() {
+        assert_eq!(
+            substituted("git commit -m <msg>", "fix"),
+            "git commit -m fix"
+        );
+        assert_eq!(
+            substituted("git commit -m <msg>", "fix bug's"),
+            r"git commit -m 'fix bug'\''s'"
+        );
+        // Quotes closed before the placeholder don't count.
+        assert_eq!(
+            substituted(r#"echo "a" 'b' \" <msg>"#, "x y"),
+            r#"echo "a" 'b' \" 'x y'"#
+        );
+    }
+
+    #[test]
+    fn escapes_values_in_double_quotes() {
+        assert_eq!(
+            substituted(r#"git commit -m "<msg>""#, "fix bug"),
+            r#"git commit -m "fix bug""#
+        );
+        assert_eq!(
+            substituted(r#"echo "say <msg>""#, r#"a "b" \ $HOME `id` 'c'"#),
+            r#"echo "say a \"b\" \\ \$HOME \`id\` 'c'""#
+        );
+    }
+
+    #[test]
+    fn escapes_values_in_single_quotes() {
+        assert_eq!(substituted("echo '<msg>'", "fix bug"), "echo 'fix bug'");
+        assert_eq!(
+            substituted(r#"echo '"<msg>'"#, r#"it's "$x""#),
+            r#"echo '"it'\''s "$x"'"#
+        );
+    }
+
+    #[test]
+    fn positions_cover_the_inserted_values() {
+        let values = HashMap::from([("msg".to_string(), "a b".to_string())]);
+        let (text, positions) = substitute(r#"echo "<msg>" <msg>"#, &values, true);
+        assert_eq!(text, r#"echo "a b" 'a b'"#);
+        assert_eq!(positions, [6, 7, 8, 11, 12, 13, 14, 15]);
+    }
+}
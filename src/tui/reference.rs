@@ -0,0 +1,174 @@
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use regex::{Captures, Regex};
+
+use super::model::Snippet;
+
+/// Matches `{{ref:<id-or-alias>}}`, capturing the id or alias.
+fn pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\{\{ref:\s*([^{}\s]+)\s*\}\}").unwrap())
+}
+
+pub(super) fn has_references(text: &str) -> bool {
+    pattern().is_match(text)
+}
+
+/// Whether `a` and `b` are the same snippet, also if one is a changed copy.
+fn same(a: &Snippet, b: &Snippet) -> bool {
+    (a.id != 0 && a.id == b.id) || (a.alias.is_some() && a.alias == b.alias)
+}
+
+/// How a snippet is named in errors: by its alias, or else its id.
+fn label(snippet: &Snippet) -> String {
+    snippet
+        .alias
+        .clone()
+        .unwrap_or_else(|| snippet.id.to_string())
+}
+
+/// Replaces every `{{ref:<id-or-alias>}}` in `text`, a command of `snippet`,
+/// with the command of the snippet with that id or alias, expanding its
+/// references in turn. References of digits only are ids.
+pub(super) fn expand(snippets: &[Snippet], snippet: &Snippet, text: &str) -> Result<String> {
+    let mut chain = vec![snippet];
+    expand_text(snippets, text, &mut chain)
+}
+
+fn expand_text<'s>(
+    snippets: &'s [Snippet],
+    text: &str,
+    chain: &mut Vec<&'s Snippet>,
+) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in pattern().captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        expanded.push_str(&text[last..whole.start()]);
+        expanded.push_str(&resolve(snippets, &caps, chain)?);
+        last = whole.end();
+    }
+    expanded.push_str(&text[last..]);
+    Ok(expanded)
+}
+
+fn resolve<'s>(
+    snippets: &'s [Snippet],
+    caps: &Captures,
+    chain: &mut Vec<&'s Snippet>,
+) -> Result<String> {
+    let name = &caps[1];
+    let target = match name.parse::<u64>() {
+        Ok(id) => match snippets.iter().find(|snippet| snippet.id == id) {
+            Some(target) => target,
+            None => bail!("No snippet has the id {} used in `{}`", id, &caps[0]),
+        },
+        Err(_) => {
+            let mut found = snippets
+                .iter()
+                .filter(|snippet| snippet.alias.as_deref() == Some(name));
+            let Some(target) = found.next() else {
+                bail!("No snippet has the alias `{}` used in `{}`", name, &caps[0]);
+            };
+            if found.next().is_some() {
+                bail!("Several snippets have the alias `{}`", name);
+            }
+            target
+        }
+    };
+
+    if let Some(start) = chain.iter().position(|snippet| same(snippet, target)) {
+        let labels: Vec<String> = chain[start..].iter().map(|s| label(s)).collect();
+        bail!(
+            "Reference cycle: {} -> {}",
+            labels.join(" -> "),
+            label(target)
+        );
+    }
+    chain.push(target);
+    let expanded = expand_text(snippets, &target.script(), chain)?;
+    chain.pop();
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(id: u64, cmd: &str, alias: Option<&str>) -> Snippet {
+        Snippet {
+            id,
+            cmd: cmd.to_string(),
+            alias: alias.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn expanded(snippets: &[Snippet], cmd: &str) -> Result<String> {
+        let user = snippet(0, cmd, None);
+        expand(snippets, &user, &user.cmd)
+    }
+
+    #[test]
+    fn expands_aliases_and_ids() {
+        let snippets = [
+            snippet(1, "docker run --rm", Some("dr")),
+            snippet(2, "{{ref:dr}} -it", None),
+        ];
+        assert_eq!(
+            expanded(&snippets, "{{ref:2}} alpine sh").unwrap(),
+            "docker run --rm -it alpine sh"
+        );
+        assert_eq!(
+            expanded(&snippets, "{{ref: 1 }} && {{ref:dr}}").unwrap(),
+            "docker run --rm && docker run --rm"
+        );
+    }
+
+    #[test]
+    fn unknown_references() {
+        let snippets = [snippet(1, "ls", Some("l"))];
+        assert_eq!(
+            expanded(&snippets, "{{ref:3}}").unwrap_err().to_string(),
+            "No snippet has the id 3 used in `{{ref:3}}`"
+        );
+        assert_eq!(
+            expanded(&snippets, "{{ref:ll}}").unwrap_err().to_string(),
+            "No snippet has the alias `ll` used in `{{ref:ll}}`"
+        );
+    }
+
+    #[test]
+    fn duplicate_aliases() {
+        let snippets = [snippet(1, "ls", Some("l")), snippet(2, "ls -l", Some("l"))];
+        assert_eq!(
+            expanded(&snippets, "{{ref:l}}").unwrap_err().to_string(),
+            "Several snippets have the alias `l`"
+        );
+        // Their ids still tell them apart.
+        assert_eq!(expanded(&snippets, "{{ref:2}}").unwrap(), "ls -l");
+    }
+
+    #[test]
+    fn self_reference() {
+        let snippets = [snippet(1, "echo {{ref:a}}", Some("a"))];
+        let err = expand(&snippets, &snippets[0], &snippets[0].cmd).unwrap_err();
+        assert_eq!(err.to_string(), "Reference cycle: a -> a");
+        let err = expand(&snippets, &snippets[0], "{{ref:1}}").unwrap_err();
+        assert_eq!(err.to_string(), "Reference cycle: a -> a");
+    }
+
+    #[test]
+    fn reference_cycle() {
+        let snippets = [
+            snippet(1, "echo {{ref:b}}", Some("a")),
+            snippet(2, "echo {{ref:1}}", Some("b")),
+        ];
+        let err = expand(&snippets, &snippets[0], &snippets[0].cmd).unwrap_err();
+        assert_eq!(err.to_string(), "Reference cycle: a -> b -> a");
+        // Also from a snippet outside the cycle.
+        let err = expanded(&snippets, "{{ref:b}}").unwrap_err();
+        assert_eq!(err.to_string(), "Reference cycle: b -> a -> b");
+    }
+}
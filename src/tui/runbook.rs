@@ -0,0 +1,258 @@
+use std::{
+    collections::HashMap,
+    io::{self, stdout, BufRead, BufReader, Write},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+
+use super::{ask_with, model::Snippet};
+
+/// What to do next, as answered by the user.
+#[derive(Clone, Copy)]
+enum Answer {
+    Run,
+    Skip,
+    Abort,
+}
+
+/// Runs a step, echoing its stdout to `out` while capturing it. Returns the
+/// exit code (`None` if killed by a signal) and the captured output.
+///
+/// Stdin and stderr stay on the terminal, but stdout is a pipe: commands that
+/// check for a terminal may leave out colors, skip their pager or refuse to prompt.
+fn run_step(
+    cmd: &str,
+    snippet: &Snippet,
+    env_vars: &HashMap<String, String>,
+    out: &mut impl Write,
+) -> Result<(Option<i32>, String)> {
+    let runner = snippet.shell.as_deref().unwrap_or("sh");
+    let mut child = Command::new(runner)
+        .arg("-c")
+        .arg(cmd)
+        .envs(env_vars)
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {runner}"))?;
+
+    let mut output = String::new();
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            writeln!(out, "{line}")?;
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+
+    let status = child.wait()?;
+    Ok((status.code(), output.trim_end().to_string()))
+}
+
+/// Runs the steps of a runbook in the restored terminal. Each step is confirmed
+/// first; a failing step stops the run until the user retries, skips it or aborts.
+///
+/// The trimmed stdout of step N is passed to later steps as `$STEP_N`. Returns
+/// the exit code of the last step run.
+pub(super) fn run(snippet: &Snippet) -> Result<Option<i32>> {
+    run_with(snippet, &mut io::stdin().lock(), &mut stdout())
+}
+
+/// `run`, reading the answers from `input` and writing to `out`.
+fn run_with(
+    snippet: &Snippet,
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> Result<Option<i32>> {
+    let total = snippet.steps.len();
+    let mut env_vars = HashMap::new();
+    let mut completed = 0;
+    let mut last_code = None;
+
+    let mut index = 0;
+    while index < total {
+        let step = &snippet.steps[index];
+        let number = index + 1;
+        writeln!(out)?;
+        if step.description.is_empty() {
+            writeln!(out, "Step {number}/{total}")?;
+        } else {
+            writeln!(out, "Step {number}/{total}: {}", step.description)?;
+        }
+        for line in step.cmd.lines() {
+            writeln!(out, "  $ {line}")?;
+        }
+
+        let answer = ask_with(
+            input,
+            out,
+            "Run this step? [Y]es, (s)kip, (a)bort:",
+            &[
+                ("y", Answer::Run),
+                ("s", Answer::Skip),
+                ("a", Answer::Abort),
+            ],
+            // Stdin is closed, nobody can confirm the next step.
+            Answer::Abort,
+        )?;
+        match answer {
+            Answer::Run => {}
+            Answer::Skip => {
+                index += 1;
+                continue;
+            }
+            Answer::Abort => break,
+        }
+
+        loop {
+            let (code, output) = run_step(&step.cmd, snippet, &env_vars, out)?;
+            env_vars.insert(format!("STEP_{number}"), output);
+            last_code = code;
+            match code {
+                Some(0) => {
+                    completed += 1;
+                    index += 1;
+                    break;
+                }
+                Some(code) => writeln!(out, "Step {number} exited with status code: {code}")?,
+                None => writeln!(out, "Step {number} was terminated by signal")?,
+            }
+
+            match ask_with(
+                input,
+                out,
+                "[R]etry the step, (s)kip it, or (a)bort the runbook:",
+                &[
+                    ("r", Answer::Run),
+                    ("s", Answer::Skip),
+                    ("a", Answer::Abort),
+                ],
+                Answer::Abort,
+            )? {
+                Answer::Run => continue,
+                Answer::Skip => {
+                    index += 1;
+                    break;
+                }
+                Answer::Abort => {
+                    writeln!(out, "\nAborted: {completed}/{total} steps completed")?;
+                    return Ok(last_code);
+                }
+            }
+        }
+    }
+
+    if index < total {
+        writeln!(out, "\nAborted: {completed}/{total} steps completed")?;
+    } else {
+        writeln!(
+            out,
+            "\nRunbook finished: {completed}/{total} steps completed"
+        )?;
+    }
+    Ok(last_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::tui::model::Step;
+
+    fn runbook(cmds: &[&str]) -> Snippet {
+        Snippet {
+            steps: cmds
+                .iter()
+                .map(|cmd| Step {
+                    cmd: cmd.to_string(),
+                    description: String::new(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Runs `snippet` with `answers` typed in. Returns the exit code and what was written.
+    fn run_answering(snippet: &Snippet, answers: &str) -> (Option<i32>, String) {
+        let mut out = Vec::new();
+        let code = run_with(snippet, &mut answers.as_bytes(), &mut out).unwrap();
+        (code, String::from_utf8(out).unwrap())
+    }
+
+    /// Whether a step printed `line`. Answers aren't echoed, so it may follow a question.
+    fn printed(out: &str, line: &str) -> bool {
+        out.lines()
+            .any(|l| l == line || l.ends_with(&format!(": {line}")))
+    }
+
+    #[test]
+    fn passes_output_to_later_steps() {
+        let snippet = runbook(&["echo one; echo ' two '", "echo \"got [$STEP_1]\""]);
+        let (code, out) = run_answering(&snippet, "y\n\n");
+        assert_eq!(code, Some(0));
+        assert!(out.contains("got [one\n two]"), "{out}");
+        assert!(
+            out.ends_with("Runbook finished: 2/2 steps completed\n"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn stops_on_failure_until_skipped() {
+        let snippet = runbook(&["exit 3", "echo after"]);
+        let (code, out) = run_answering(&snippet, "y\ns\ny\n");
+        assert_eq!(code, Some(0));
+        assert!(out.contains("Step 1 exited with status code: 3"), "{out}");
+        assert!(printed(&out, "after"), "{out}");
+        assert!(
+            out.ends_with("Runbook finished: 1/2 steps completed\n"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn retries_a_failed_step() {
+        let flag = env::temp_dir().join(format!("chopsticks-test-retry-{}", process::id()));
+        let _ = fs::remove_file(&flag);
+        let flag = flag.display();
+        // Fails the first time only.
+        let snippet = runbook(&[&format!("[ -e {flag} ] || {{ touch {flag}; exit 1; }}")]);
+        let (code, out) = run_answering(&snippet, "y\nr\n");
+        fs::remove_file(flag.to_string()).unwrap();
+        assert_eq!(code, Some(0));
+        assert_eq!(
+            out.matches("exited with status code: 1").count(),
+            1,
+            "{out}"
+        );
+        assert!(
+            out.ends_with("Runbook finished: 1/1 steps completed\n"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn aborts_after_a_failure() {
+        let snippet = runbook(&["echo first", "exit 4", "echo never"]);
+        let (code, out) = run_answering(&snippet, "y\ny\na\n");
+        assert_eq!(code, Some(4));
+        assert!(printed(&out, "first") && !printed(&out, "never"), "{out}");
+        assert!(out.ends_with("Aborted: 1/3 steps completed\n"), "{out}");
+    }
+
+    #[test]
+    fn skips_and_aborts_before_a_step() {
+        let snippet = runbook(&["echo one", "echo two", "echo three"]);
+        let (code, out) = run_answering(&snippet, "s\ny\na\n");
+        assert_eq!(code, Some(0));
+        assert!(!printed(&out, "one") && printed(&out, "two"), "{out}");
+        assert!(out.ends_with("Aborted: 1/3 steps completed\n"), "{out}");
+
+        // Unknown answers are asked again, and closed stdin aborts.
+        let (code, out) = run_answering(&snippet, "x\n");
+        assert_eq!(code, None);
+        assert_eq!(out.matches("Run this step?").count(), 2, "{out}");
+        assert!(out.ends_with("Aborted: 0/3 steps completed\n"), "{out}");
+    }
+}
@@ -0,0 +1,496 @@
+use std::fmt::Display;
+
+use anyhow::{anyhow, Result};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use regex::{Regex, RegexBuilder};
+
+use crate::config::Weights;
+
+/// How a plain search term is matched. Query syntax like `'exact` or `^prefix`
+/// overrides the mode for a single term.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchMode {
+    #[default]
+    Fuzzy,
+    Exact,
+    Prefix,
+    Regex,
+}
+
+impl SearchMode {
+    pub fn next(self) -> Self {
+        match self {
+            SearchMode::Fuzzy => SearchMode::Exact,
+            SearchMode::Exact => SearchMode::Prefix,
+            SearchMode::Prefix => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::Fuzzy,
+        }
+    }
+}
+
+impl Display for SearchMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SearchMode::Fuzzy => "Fuzzy",
+            SearchMode::Exact => "Exact",
+            SearchMode::Prefix => "Prefix",
+            SearchMode::Regex => "Regex",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How the list is ordered while the search bar is empty. Search results are
+/// ordered by score.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Priority,
+    Alphabetical,
+    Newest,
+    RecentlyUpdated,
+    RecentlyUsed,
+    MostUsed,
+}
+
+impl SortOrder {
+    pub fn next(self) -> Self {
+        match self {
+            SortOrder::Priority => SortOrder::Alphabetical,
+            SortOrder::Alphabetical => SortOrder::Newest,
+            SortOrder::Newest => SortOrder::RecentlyUpdated,
+            SortOrder::RecentlyUpdated => SortOrder::RecentlyUsed,
+            SortOrder::RecentlyUsed => SortOrder::MostUsed,
+            SortOrder::MostUsed => SortOrder::Priority,
+        }
+    }
+}
+
+impl Display for SortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SortOrder::Priority => "Priority",
+            SortOrder::Alphabetical => "Alphabetical",
+            SortOrder::Newest => "Newest",
+            SortOrder::RecentlyUpdated => "Recently updated",
+            SortOrder::RecentlyUsed => "Recently used",
+            SortOrder::MostUsed => "Most used",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug)]
+enum Pattern {
+    Fuzzy(String),
+    /// Substring match, optionally anchored to the start and/or end of the field.
+    Exact {
+        text: Vec<char>,
+        prefix: bool,
+        suffix: bool,
+    },
+    Regex(Regex),
+}
+
+/// A searchable snippet field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Field {
+    Cmd,
+    Description,
+    Tags,
+    Shell,
+    Dir,
+}
+
+/// Matches field qualifiers in the search bar, for highlighting.
+pub(super) const QUALIFIER_PATTERN: &str = r"!?\b(cmd|desc|description|tag|tags|shell|dir):\S*";
+
+impl Field {
+    fn from_qualifier(qualifier: &str) -> Option<Self> {
+        match qualifier {
+            "cmd" => Some(Field::Cmd),
+            "desc" | "description" => Some(Field::Description),
+            "tag" | "tags" => Some(Field::Tags),
+            "shell" => Some(Field::Shell),
+            "dir" => Some(Field::Dir),
+            _ => None,
+        }
+    }
+
+    fn weight(self, weights: &Weights) -> f64 {
+        match self {
+            Field::Cmd => weights.cmd,
+            Field::Description => weights.description,
+            Field::Tags => weights.tags,
+            Field::Shell => weights.shell,
+            Field::Dir => weights.dir,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Term {
+    pattern: Pattern,
+    negated: bool,
+    /// The field this term is limited to. Unqualified terms match `cmd` and `description`.
+    field: Option<Field>,
+}
+
+/// A parsed search bar query. Terms are separated by whitespace and must all match.
+#[derive(Debug, Default)]
+pub(super) struct Query {
+    terms: Vec<Term>,
+}
+
+/// Smart case, as in fzf: a term is case-sensitive only if it contains an uppercase char.
+fn is_case_sensitive(term: &str) -> bool {
+    term.chars().any(char::is_uppercase)
+}
+
+impl Query {
+    /// Parses fzf-style syntax: `'exact`, `^prefix`, `suffix$` and `!negation`,
+    /// optionally after a field qualifier like `cmd:` or `tag:`.
+    /// Everything else is matched according to `mode`.
+    pub fn parse(input: &str, mode: SearchMode) -> Result<Self> {
+        let terms = input
+            .split_ascii_whitespace()
+            .filter_map(|term| {
+                let (negated, term) = match term.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, term),
+                };
+                let (field, term) = match term.split_once(':') {
+                    Some((qualifier, rest)) => match Field::from_qualifier(qualifier) {
+                        Some(field) => (Some(field), rest),
+                        None => (None, term),
+                    },
+                    None => (None, term),
+                };
+                if term.is_empty() {
+                    return None;
+                }
+                Some(Self::parse_term(term, mode).map(|pattern| Term {
+                    pattern,
+                    negated,
+                    field,
+                }))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { terms })
+    }
+
+    fn parse_term(term: &str, mode: SearchMode) -> Result<Pattern> {
+        if mode == SearchMode::Regex {
+            let regex = RegexBuilder::new(term)
+                .case_insensitive(!is_case_sensitive(term))
+                .build()
+                .map_err(|err| {
+                    // Syntax errors span several lines, the last one names the problem.
+                    let err = err.to_string();
+                    anyhow!(
+                        "Invalid regex `{}`: {}",
+                        term,
+                        err.lines().last().unwrap_or("")
+                    )
+                })?;
+            return Ok(Pattern::Regex(regex));
+        }
+
+        let exact = |text: &str, prefix, suffix| Pattern::Exact {
+            text: text.chars().collect(),
+            prefix,
+            suffix,
+        };
+
+        let pattern = if let Some(rest) = term.strip_prefix('\'') {
+            exact(rest, false, false)
+        } else {
+            let (prefix, term) = match term.strip_prefix('^') {
+                Some(rest) => (true, rest),
+                None => (false, term),
+            };
+            let (suffix, term) = match term.strip_suffix('$') {
+                Some(rest) if !rest.is_empty() => (true, rest),
+                _ => (false, term),
+            };
+            match mode {
+                _ if prefix || suffix => exact(term, prefix, suffix),
+                SearchMode::Fuzzy => Pattern::Fuzzy(term.to_string()),
+                SearchMode::Exact => exact(term, false, false),
+                SearchMode::Prefix | SearchMode::Regex => exact(term, true, false),
+            }
+        };
+
+        Ok(pattern)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Matches every term against `fields`. Returns `None` if a term matches no
+    /// field or a negated term matches any, otherwise the weighted score and the
+    /// matched char positions of each field.
+    pub fn matches(
+        &self,
+        matcher: &SkimMatcherV2,
+        weights: &Weights,
+        fields: &[(Field, &str)],
+    ) -> Option<(i64, Vec<Vec<usize>>)> {
+        let mut score = 0;
+        let mut positions = vec![Vec::new(); fields.len()];
+
+        for term in &self.terms {
+            let mut matched = false;
+            for ((field, text), field_positions) in fields.iter().zip(positions.iter_mut()) {
+                let in_scope = match term.field {
+                    Some(scope) => scope == *field,
+                    None => matches!(field, Field::Cmd | Field::Description),
+                };
+                if !in_scope {
+                    continue;
+                }
+                if let Some((term_score, indices)) = term.pattern.find(matcher, text) {
+                    matched = true;
+                    if !term.negated {
+                        score += (term_score as f64 * field.weight(weights)).round() as i64;
+                        field_positions.extend(indices);
+                    }
+                }
+            }
+            if matched == term.negated {
+                return None;
+            }
+        }
+
+        for field_positions in &mut positions {
+            field_positions.sort_unstable();
+            field_positions.dedup();
+        }
+        Some((score, positions))
+    }
+}
+
+impl Pattern {
+    /// Returns the score and matched char positions of this pattern in `text`.
+    fn find(&self, matcher: &SkimMatcherV2, text: &str) -> Option<(i64, Vec<usize>)> {
+        match self {
+            Pattern::Fuzzy(pattern) => matcher.fuzzy_indices(text, pattern),
+            Pattern::Exact {
+                text: pattern,
+                prefix,
+                suffix,
+            } => {
+                let case_sensitive = pattern.iter().any(|c| c.is_uppercase());
+                let eq = |a: &char, b: &char| {
+                    if case_sensitive {
+                        a == b
+                    } else {
+                        a.to_lowercase().eq(b.to_lowercase())
+                    }
+                };
+                let chars: Vec<char> = text.chars().collect();
+                if pattern.len() > chars.len() {
+                    return None;
+                }
+                let last = chars.len() - pattern.len();
+                // Range of start positions the anchors allow.
+                let (first, last) = match (prefix, suffix) {
+                    (true, true) if last == 0 => (0, 0),
+                    (true, true) => return None,
+                    (true, false) => (0, 0),
+                    (false, true) => (last, last),
+                    (false, false) => (0, last),
+                };
+                let start = (first..=last)
+                    .find(|&s| chars[s..].iter().zip(pattern).all(|(a, b)| eq(a, b)))?;
+                // Longer and earlier matches rank higher.
+                let score = pattern.len() as i64 * 16 - start.min(15) as i64;
+                Some((score, (start..start + pattern.len()).collect()))
+            }
+            Pattern::Regex(regex) => {
+                let m = regex.find(text)?;
+                let start = text[..m.start()].chars().count();
+                let len = m.as_str().chars().count();
+                let score = len as i64 * 16 - start.min(15) as i64;
+                Some((score, (start..start + len).collect()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The cmd positions `query` matches in `cmd`, with `desc` as the description.
+    fn find(query: &str, mode: SearchMode, cmd: &str, desc: &str) -> Option<Vec<usize>> {
+        let query = Query::parse(query, mode).unwrap();
+        let fields = [(Field::Cmd, cmd), (Field::Description, desc)];
+        let (_, positions) =
+            query.matches(&SkimMatcherV2::default(), &Weights::default(), &fields)?;
+        Some(positions[0].clone())
+    }
+
+    #[test]
+    fn operators() {
+        let cases: &[(&str, &str, Option<&[usize]>)] = &[
+            // Fuzzy by default.
+            ("gst", "git status", Some(&[0, 4, 5])),
+            ("'tst", "git status", None),
+            ("tst", "git status", Some(&[2, 4, 5])),
+            ("'it", "git status", Some(&[1, 2])),
+            ("'gst", "git status", None),
+            ("^git", "git status", Some(&[0, 1, 2])),
+            ("^status", "git status", None),
+            ("tus$", "git status", Some(&[7, 8, 9])),
+            ("git$", "git status", None),
+            ("^git$", "git", Some(&[0, 1, 2])),
+            ("^git$", "git status", None),
+            ("!docker", "git status", Some(&[])),
+            ("!git", "git status", None),
+            ("!^status", "git status", Some(&[])),
+            ("!^git", "git status", None),
+            // A lone `$` is matched as it is.
+            ("$", "echo $HOME", Some(&[5])),
+            // Smart case.
+            ("'Status", "git status", None),
+            ("'STATUS", "git STATUS", Some(&[4, 5, 6, 7, 8, 9])),
+        ];
+        for &(query, cmd, expected) in cases {
+            let found = find(query, SearchMode::Fuzzy, cmd, "");
+            assert_eq!(found.as_deref(), expected, "`{query}` in `{cmd}`");
+        }
+    }
+
+    #[test]
+    fn combined_terms_must_all_match() {
+        let cases: &[(&str, &str, Option<&[usize]>)] = &[
+            ("^git tus$", "git status", Some(&[0, 1, 2, 7, 8, 9])),
+            ("^git 'push", "git status", None),
+            ("^git !push", "git status", Some(&[0, 1, 2])),
+            ("^git !push", "git push", None),
+            ("'sta !'stash tus$", "git status", Some(&[4, 5, 6, 7, 8, 9])),
+            ("'sta !'stash", "git stash", None),
+        ];
+        for &(query, cmd, expected) in cases {
+            let found = find(query, SearchMode::Fuzzy, cmd, "");
+            assert_eq!(found.as_deref(), expected, "`{query}` in `{cmd}`");
+        }
+    }
+
+    #[test]
+    fn modes() {
+        let cases: &[(SearchMode, &str, &str, Option<&[usize]>)] = &[
+            (SearchMode::Exact, "stat", "git status", Some(&[4, 5, 6, 7])),
+            (SearchMode::Exact, "gst", "git status", None),
+            (SearchMode::Prefix, "git", "git status", Some(&[0, 1, 2])),
+            (SearchMode::Prefix, "status", "git status", None),
+            (SearchMode::Regex, "s.a", "git status", Some(&[4, 5, 6])),
+            (SearchMode::Regex, "^s", "git status", None),
+            // Operators override the mode.
+            (SearchMode::Exact, "tus$", "git status", Some(&[7, 8, 9])),
+            (
+                SearchMode::Prefix,
+                "'stat",
+                "git status",
+                Some(&[4, 5, 6, 7]),
+            ),
+        ];
+        for &(mode, query, cmd, expected) in cases {
+            let found = find(query, mode, cmd, "");
+            assert_eq!(found.as_deref(), expected, "`{query}` in {mode} mode");
+        }
+    }
+
+    #[test]
+    fn invalid_regex() {
+        let err = Query::parse("git (", SearchMode::Regex).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid regex `(`: "), "{err}");
+        assert!(Query::parse("git (", SearchMode::Fuzzy).is_ok());
+    }
+
+    const FIELDS: [(Field, &str); 5] = [
+        (Field::Cmd, "docker compose up"),
+        (Field::Description, "Start the stack"),
+        (Field::Tags, "docker dev"),
+        (Field::Shell, "bash"),
+        (Field::Dir, "/srv/app"),
+    ];
+
+    #[test]
+    fn qualifiers() {
+        let cases: &[(&str, bool)] = &[
+            ("stack", true),
+            // Unqualified terms only look at the command and description.
+            ("'dev", false),
+            ("bash", false),
+            ("cmd:'compose", true),
+            ("cmd:'stack", false),
+            ("desc:^start", true),
+            ("description:'stack", true),
+            ("desc:'compose", false),
+            ("tag:'dev", true),
+            ("tags:^docker", true),
+            ("tag:'prod", false),
+            ("shell:bash$", true),
+            ("shell:'zsh", false),
+            ("dir:'/srv", true),
+            ("dir:'/tmp", false),
+            ("!tag:'prod", true),
+            ("!tag:'dev", false),
+            ("cmd:'up tag:'dev !dir:'/tmp", true),
+            // Unknown qualifiers are part of the term.
+            ("foo:bar", false),
+            ("cmd:", true),
+        ];
+        for &(query, expected) in cases {
+            let parsed = Query::parse(query, SearchMode::Fuzzy).unwrap();
+            let found = parsed.matches(&SkimMatcherV2::default(), &Weights::default(), &FIELDS);
+            assert_eq!(found.is_some(), expected, "`{query}`");
+        }
+    }
+
+    #[test]
+    fn qualified_positions() {
+        let query = Query::parse("tag:'dev desc:'stack", SearchMode::Fuzzy).unwrap();
+        let (_, positions) = query
+            .matches(&SkimMatcherV2::default(), &Weights::default(), &FIELDS)
+            .unwrap();
+        assert!(positions[0].is_empty());
+        assert_eq!(positions[1], [10, 11, 12, 13, 14]);
+        assert_eq!(positions[2], [7, 8, 9]);
+    }
+
+    #[test]
+    fn weights_scale_the_scores() {
+        let score = |query: &str, weights: &Weights| {
+            let query = Query::parse(query, SearchMode::Fuzzy).unwrap();
+            let fields = [(Field::Cmd, "git stash"), (Field::Description, "Stash")];
+            query
+                .matches(&SkimMatcherV2::default(), weights, &fields)
+                .unwrap()
+                .0
+        };
+        let weights = Weights {
+            cmd: 2.0,
+            description: 0.5,
+            ..Default::default()
+        };
+        let plain = Weights::default();
+        assert_eq!(
+            score("cmd:'stash", &weights),
+            2 * score("cmd:'stash", &plain)
+        );
+        assert_eq!(
+            score("desc:'stash", &weights) * 2,
+            score("desc:'stash", &plain)
+        );
+        // Both fields add up, each with its own weight.
+        assert_eq!(
+            score("'stash", &weights),
+            score("cmd:'stash", &weights) + score("desc:'stash", &weights)
+        );
+    }
+}
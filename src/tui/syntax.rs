@@ -0,0 +1,244 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+use regex::Regex;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::config::Validation;
+
+use super::{highlight::is_shell, model::Snippet, placeholder};
+
+/// Shells whose scripts shellcheck understands, as its `--shell` dialect.
+const SHELLCHECK_DIALECTS: [(&str, &str); 4] = [
+    ("sh", "sh"),
+    ("bash", "bash"),
+    ("dash", "dash"),
+    ("ksh", "ksh"),
+];
+
+/// A problem found in a command, at a line of the editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Warning {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Warnings about the editor content `source`. Saving the same source again saves it anyway.
+#[derive(Debug)]
+pub(super) struct Report {
+    pub source: String,
+    pub warnings: Vec<Warning>,
+}
+
+/// Where the commands are in the editor content.
+#[derive(Deserialize)]
+struct Spans {
+    cmd: Option<Spanned<String>>,
+    #[serde(default)]
+    steps: Vec<StepSpans>,
+}
+
+#[derive(Deserialize)]
+struct StepSpans {
+    cmd: Spanned<String>,
+}
+
+/// Finds the line number in messages like `bash: line 3: syntax error`,
+/// `sh: 3: Syntax error`, `zsh:3: parse error` or `-:3:5: warning`.
+fn line_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(?:.*?line |[^:]*:\s*)(\d+):(?:\d+:)?\s*(.*)$").unwrap())
+}
+
+/// Checks the commands of `snippet`, parsed from the editor content `source`,
+/// with the shell's syntax check and shellcheck, as enabled in `validation`.
+pub(super) fn check(source: &str, snippet: &Snippet, validation: &Validation) -> Vec<Warning> {
+    if !is_shell(snippet.shell.as_deref()) {
+        return Vec::new();
+    }
+    let Ok(spans) = toml::from_str::<Spans>(source) else {
+        return Vec::new();
+    };
+
+    let commands = spans
+        .cmd
+        .iter()
+        .chain(spans.steps.iter().map(|step| &step.cmd))
+        .filter(|cmd| !cmd.get_ref().trim().is_empty());
+
+    // Placeholders would read as redirections.
+    let values: HashMap<String, String> = placeholder::names(snippet)
+        .into_iter()
+        .map(|name| (name, "placeholder".to_string()))
+        .collect();
+
+    let shell = snippet.shell.as_deref().unwrap_or("sh");
+    let name = shell.rsplit('/').next().unwrap_or(shell);
+    let dialect = SHELLCHECK_DIALECTS
+        .iter()
+        .find(|(shell, _)| *shell == name)
+        .map(|(_, dialect)| *dialect);
+
+    let mut warnings = Vec::new();
+    for cmd in commands {
+        let first_line = first_line(source, cmd.span().start);
+        let script = placeholder::substitute(cmd.get_ref(), &values, false).0;
+        let mut messages = Vec::new();
+        if validation.syntax {
+            messages.extend(run(shell, &["-n"], &script, name));
+        }
+        if let Some(dialect) = dialect.filter(|_| validation.shellcheck) {
+            let shell_arg = format!("--shell={dialect}");
+            messages.extend(run(
+                "shellcheck",
+                &["--format=gcc", &shell_arg, "-"],
+                &script,
+                "shellcheck",
+            ));
+        }
+        warnings.extend(messages.into_iter().map(|(line, message)| Warning {
+            line: editor_line(first_line, &script, line),
+            message,
+        }));
+    }
+    warnings.sort_by_key(|warning| warning.line);
+    warnings
+}
+
+/// The editor line where the `cmd` of the snippet in `source` starts.
+pub(super) fn cmd_line(source: &str) -> usize {
+    toml::from_str::<Spans>(source)
+        .ok()
+        .and_then(|spans| spans.cmd)
+        .map_or(0, |cmd| first_line(source, cmd.span().start))
+}
+
+/// The editor line of the first line of a string value starting at byte `start`.
+/// A multi-line string skips a newline right after its opening quotes.
+fn first_line(source: &str, start: usize) -> usize {
+    let line = source[..start].matches('\n').count();
+    let value = &source[start..];
+    if value.starts_with("'''\n") || value.starts_with("\"\"\"\n") {
+        line + 1
+    } else {
+        line
+    }
+}
+
+/// The editor line of `line` of `script`, whose first line is at `first_line`.
+/// Lines past the end, like the end of file of an unterminated quote, are
+/// on its last line, and messages without a line on its first.
+fn editor_line(first_line: usize, script: &str, line: Option<usize>) -> usize {
+    let last = script.lines().count().max(1);
+    first_line + line.unwrap_or(1).clamp(1, last) - 1
+}
+
+/// Runs `program` on `script` and returns its complaints, with the script line
+/// each is about if it names one. A program that isn't installed finds nothing.
+fn run(program: &str, args: &[&str], script: &str, name: &str) -> Vec<(Option<usize>, String)> {
+    let Ok(mut child) = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    else {
+        return Vec::new();
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(script.as_bytes());
+    }
+    let Ok(output) = child.wait_with_output() else {
+        return Vec::new();
+    };
+    if output.status.success() {
+        return Vec::new();
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout) + String::from_utf8_lossy(&output.stderr);
+    parse(&text, name)
+}
+
+/// The complaints in the output of `name`, with the line each is about.
+fn parse(text: &str, name: &str) -> Vec<(Option<usize>, String)> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match line_pattern().captures(line) {
+            Some(caps) => (caps[1].parse().ok(), format!("{name}: {}", &caps[2])),
+            None => (None, format!("{name}: {line}")),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_shell_messages() {
+        let text = "\
+sh: 3: Syntax error: Unterminated quoted string
+bash: line 2: syntax error: unexpected end of file
+
+zsh:4: parse error near `fi'
+sh: cannot open x: No such file
+";
+        assert_eq!(
+            parse(text, "sh"),
+            [
+                (
+                    Some(3),
+                    "sh: Syntax error: Unterminated quoted string".into()
+                ),
+                (Some(2), "sh: syntax error: unexpected end of file".into()),
+                (Some(4), "sh: parse error near `fi'".into()),
+                (None, "sh: sh: cannot open x: No such file".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_shellcheck_messages() {
+        let text = "\
+-:1:6: warning: Quote this to prevent word splitting. [SC2046]
+-:12:1: error: Couldn't parse this if expression. [SC1073]
+";
+        assert_eq!(
+            parse(text, "shellcheck"),
+            [
+                (
+                    Some(1),
+                    "shellcheck: warning: Quote this to prevent word splitting. [SC2046]".into()
+                ),
+                (
+                    Some(12),
+                    "shellcheck: error: Couldn't parse this if expression. [SC1073]".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn places_lines_in_the_editor() {
+        let script = "echo \"a\nb";
+        assert_eq!(editor_line(4, script, Some(1)), 4);
+        assert_eq!(editor_line(4, script, Some(2)), 5);
+        // The end of file after the last line.
+        assert_eq!(editor_line(4, script, Some(3)), 5);
+        assert_eq!(editor_line(4, script, Some(0)), 4);
+        assert_eq!(editor_line(4, script, None), 4);
+        assert_eq!(editor_line(0, "", Some(2)), 0);
+    }
+
+    #[test]
+    fn finds_the_first_line_of_commands() {
+        let source = "description = \"x\"\ncmd = \"\"\"\nls\n\"\"\"\n";
+        assert_eq!(cmd_line(source), 2);
+        assert_eq!(cmd_line("cmd = 'ls'\n"), 0);
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    env,
+    fmt::{self, Display, Formatter},
+    process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{highlight::is_shell, model::Snippet};
+
+/// A tmux pane to run a snippet in, instead of chopsticks' terminal.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// The pane chopsticks was opened from, in a tmux popup.
+    Pane,
+    /// A new pane, split from the current one.
+    Split,
+    Window,
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pane => "pane",
+            Self::Split => "split",
+            Self::Window => "window",
+        })
+    }
+}
+
+/// Whether chopsticks runs inside tmux.
+pub(super) fn inside() -> bool {
+    env::var_os("TMUX").is_some()
+}
+
+/// A tmux server: the one chopsticks runs in, unless a socket is given.
+#[derive(Debug, Default)]
+pub(super) struct Tmux {
+    /// The `-L` socket name of another server.
+    socket: Option<String>,
+}
+
+impl Tmux {
+    /// Runs a tmux command, returning its trimmed output.
+    fn tmux(&self, args: &[&str]) -> Result<String> {
+        let mut command = Command::new("tmux");
+        if let Some(socket) = &self.socket {
+            command.args(["-L", socket]);
+        }
+        let output = command.args(args).output().context("Failed to run tmux")?;
+        if !output.status.success() {
+            bail!(
+                "tmux {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// The pane chopsticks was opened from. From a popup, that's the active pane below it.
+    fn origin(&self) -> Result<String> {
+        let pane = self.tmux(&["display-message", "-p", "#{pane_id}"])?;
+        if self.socket.is_none() && env::var("TMUX_PANE").is_ok_and(|own| own == pane) {
+            bail!("Chopsticks isn't in a tmux popup, there's no pane to send the command to");
+        }
+        Ok(pane)
+    }
+
+    /// Types the command of `snippet` into the shell of the `target` pane and
+    /// runs it. Returns the pane's id.
+    pub fn run(&self, target: Target, snippet: &Snippet) -> Result<String> {
+        if self.socket.is_none() && !inside() {
+            bail!("Chopsticks isn't running inside tmux");
+        }
+        if snippet.is_runbook() {
+            bail!("Runbooks need confirmation at each step, so they only run in chopsticks");
+        }
+        if !is_shell(snippet.shell.as_deref()) {
+            bail!("Only shell commands can be sent to a tmux pane");
+        }
+
+        let pane = match target {
+            Target::Pane => self.origin()?,
+            Target::Split | Target::Window => {
+                let command = match target {
+                    Target::Split => "split-window",
+                    _ => "new-window",
+                };
+                self.tmux(&[command, "-P", "-F", "#{pane_id}"])?
+            }
+        };
+
+        self.tmux(&["send-keys", "-t", &pane, "-l", "--", &snippet.cmd])?;
+        self.tmux(&["send-keys", "-t", &pane, "Enter"])?;
+        Ok(pane)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        process,
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    /// A tmux server of its own on a private socket, with a detached session
+    /// running `sh`. Killed when dropped.
+    struct Server(Tmux);
+
+    impl Server {
+        /// Starts the server, unless tmux isn't installed.
+        fn start(name: &str) -> Option<Self> {
+            Command::new("tmux").arg("-V").output().ok()?;
+            let server = Self(Tmux {
+                socket: Some(format!("chopsticks-test-{}-{name}", process::id())),
+            });
+            server
+                .0
+                .tmux(&[
+                    "-f",
+                    "/dev/null",
+                    "new-session",
+                    "-d",
+                    "-x",
+                    "80",
+                    "-y",
+                    "24",
+                    "sh",
+                ])
+                .unwrap();
+            server
+                .0
+                .tmux(&["set-option", "-g", "default-shell", "/bin/sh"])
+                .unwrap();
+            Some(server)
+        }
+
+        /// Waits for a line of the pane's screen to end with `output`. Keys sent
+        /// before the shell started are echoed before its prompt, so the output
+        /// may follow a prompt.
+        fn wait_for(&self, pane: &str, output: &str) -> bool {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while Instant::now() < deadline {
+                let screen = self.0.tmux(&["capture-pane", "-p", "-t", pane]).unwrap();
+                if screen.lines().any(|line| line.trim_end().ends_with(output)) {
+                    return true;
+                }
+                sleep(Duration::from_millis(20));
+            }
+            false
+        }
+
+        fn count(&self, what: &str) -> usize {
+            self.0.tmux(&[what]).unwrap().lines().count()
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = self.0.tmux(&["kill-server"]);
+        }
+    }
+
+    fn snippet(cmd: &str) -> Snippet {
+        Snippet {
+            cmd: cmd.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sends_to_the_origin_pane() {
+        let Some(server) = Server::start("pane") else {
+            return;
+        };
+        let origin = server.0.origin().unwrap();
+        let snippet = snippet("echo sent-$((6 * 7))");
+        let pane = server.0.run(Target::Pane, &snippet).unwrap();
+        assert_eq!(pane, origin);
+        assert!(server.wait_for(&pane, "sent-42"));
+        assert_eq!(server.count("list-panes"), 1);
+    }
+
+    #[test]
+    fn runs_in_a_new_split() {
+        let Some(server) = Server::start("split") else {
+            return;
+        };
+        let pane = server
+            .0
+            .run(Target::Split, &snippet("echo split-$((2 * 3))"))
+            .unwrap();
+        assert!(server.wait_for(&pane, "split-6"));
+        assert_eq!(server.count("list-panes"), 2);
+    }
+
+    #[test]
+    fn runs_in_a_new_window() {
+        let Some(server) = Server::start("window") else {
+            return;
+        };
+        let pane = server
+            .0
+            .run(Target::Window, &snippet("echo $((1 + 1))"))
+            .unwrap();
+        assert!(server.wait_for(&pane, "2"));
+        assert_eq!(server.count("list-windows"), 2);
+    }
+
+    #[test]
+    fn refuses_other_interpreters() {
+        let mut snippet = snippet("print(1)");
+        snippet.shell = Some("python3".to_string());
+        let tmux = Tmux {
+            socket: Some("unused".to_string()),
+        };
+        assert!(tmux.run(Target::Split, &snippet).is_err());
+    }
+}
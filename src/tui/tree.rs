@@ -0,0 +1,247 @@
+use std::collections::{BTreeMap, HashSet};
+
+use super::model::{Hit, Snippet};
+
+/// A row of the category tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Row {
+    Category {
+        /// Full path, like `k8s/debug`.
+        path: String,
+        depth: usize,
+        /// Snippets listed anywhere below it.
+        count: usize,
+        expanded: bool,
+    },
+    Snippet {
+        /// Position in `App::hits`.
+        hit: usize,
+        depth: usize,
+    },
+}
+
+impl Row {
+    pub fn depth(&self) -> usize {
+        match self {
+            Row::Category { depth, .. } | Row::Snippet { depth, .. } => *depth,
+        }
+    }
+}
+
+/// Splits a category path into its non-empty parts, so `k8s//debug/` is `k8s/debug`.
+pub(super) fn parts(category: &str) -> Vec<&str> {
+    category
+        .split('/')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Whether `category` is `scope` or below it.
+pub(super) fn in_scope(category: Option<&str>, scope: &str) -> bool {
+    let scope = parts(scope);
+    let category = category.map(parts).unwrap_or_default();
+    category.starts_with(&scope)
+}
+
+/// The category one level above `path`, or `None` at the top.
+pub(super) fn parent(path: &str) -> Option<String> {
+    let parts = parts(path);
+    (parts.len() > 1).then(|| parts[..parts.len() - 1].join("/"))
+}
+
+/// `category` with its leading `from` replaced by `to`, if it is in `from`.
+/// An empty `to` moves the subtree to the top.
+pub(super) fn rename(category: &str, from: &str, to: &str) -> Option<String> {
+    let rest = parts(category);
+    let from = parts(from);
+    if !rest.starts_with(&from) {
+        return None;
+    }
+    let mut renamed = parts(to);
+    renamed.extend(&rest[from.len()..]);
+    Some(renamed.join("/"))
+}
+
+#[derive(Default)]
+struct Node {
+    children: BTreeMap<String, Node>,
+    hits: Vec<usize>,
+    count: usize,
+}
+
+/// Lays the hits out as a tree of their categories, below `scope`. Categories
+/// come before the snippets of their parent, sorted by name; snippets keep the
+/// order of the hits. Only the categories in `expanded` show their content,
+/// unless `expand_all`.
+pub(super) fn rows(
+    hits: &[Hit],
+    snippets: &[Snippet],
+    scope: Option<&str>,
+    expanded: &HashSet<String>,
+    expand_all: bool,
+) -> Vec<Row> {
+    let skip = scope.map(|scope| parts(scope).len()).unwrap_or_default();
+    let mut root = Node::default();
+    for (i, hit) in hits.iter().enumerate() {
+        let category = snippets[hit.index].category.as_deref().map(parts);
+        let mut node = &mut root;
+        node.count += 1;
+        for part in category.unwrap_or_default().into_iter().skip(skip) {
+            node = node.children.entry(part.to_string()).or_default();
+            node.count += 1;
+        }
+        node.hits.push(i);
+    }
+
+    let mut rows = Vec::new();
+    let prefix: Vec<&str> = scope.map(parts).unwrap_or_default();
+    flatten(&root, &prefix.join("/"), 0, expanded, expand_all, &mut rows);
+    rows
+}
+
+fn flatten(
+    node: &Node,
+    path: &str,
+    depth: usize,
+    expanded: &HashSet<String>,
+    expand_all: bool,
+    rows: &mut Vec<Row>,
+) {
+    for (name, child) in &node.children {
+        let path = if path.is_empty() {
+            name.clone()
+        } else {
+            format!("{path}/{name}")
+        };
+        let is_expanded = expand_all || expanded.contains(&path);
+        rows.push(Row::Category {
+            path: path.clone(),
+            depth,
+            count: child.count,
+            expanded: is_expanded,
+        });
+        if is_expanded {
+            flatten(child, &path, depth + 1, expanded, expand_all, rows);
+        }
+    }
+    rows.extend(node.hits.iter().map(|&hit| Row::Snippet { hit, depth }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_paths() {
+        assert_eq!(parts(" k8s//debug/ "), ["k8s", "debug"]);
+        assert!(parts("/").is_empty());
+        assert_eq!(parent("a/b/c").as_deref(), Some("a/b"));
+        assert_eq!(parent("a/").as_deref(), None);
+    }
+
+    #[test]
+    fn scopes_at_part_boundaries() {
+        assert!(in_scope(Some("a/b"), "a"));
+        assert!(in_scope(Some("a"), "a/"));
+        assert!(!in_scope(Some("ab"), "a"));
+        assert!(!in_scope(Some("a"), "a/b"));
+        assert!(!in_scope(None, "a"));
+        assert!(in_scope(None, ""));
+    }
+
+    #[test]
+    fn renames_nested_categories() {
+        assert_eq!(rename("a/b", "a", "x").as_deref(), Some("x/b"));
+        assert_eq!(rename("a/b/c", "a/b", "x").as_deref(), Some("x/c"));
+        assert_eq!(rename("ab", "a", "x"), None);
+        assert_eq!(rename("a/b", "a", "").as_deref(), Some("b"));
+    }
+
+    fn snippets(categories: &[Option<&str>]) -> (Vec<Hit>, Vec<Snippet>) {
+        let snippets = categories
+            .iter()
+            .map(|category| Snippet {
+                category: category.map(str::to_string),
+                ..Default::default()
+            })
+            .collect();
+        let hits = (0..categories.len())
+            .map(|index| Hit {
+                index,
+                ..Default::default()
+            })
+            .collect();
+        (hits, snippets)
+    }
+
+    fn category(path: &str, depth: usize, count: usize, expanded: bool) -> Row {
+        Row::Category {
+            path: path.into(),
+            depth,
+            count,
+            expanded,
+        }
+    }
+
+    #[test]
+    fn flattens_expanded_categories() {
+        let (hits, snippets) = snippets(&[Some("a/b"), Some("ab"), Some("a"), None, Some("a/b/c")]);
+        let rows_with = |expanded: &[&str], expand_all| {
+            let expanded = expanded.iter().map(|path| path.to_string()).collect();
+            rows(&hits, &snippets, None, &expanded, expand_all)
+        };
+
+        assert_eq!(
+            rows_with(&[], false),
+            [
+                category("a", 0, 3, false),
+                category("ab", 0, 1, false),
+                Row::Snippet { hit: 3, depth: 0 },
+            ]
+        );
+        // A collapsed category hides what's below, even if that's expanded.
+        assert_eq!(rows_with(&["a/b"], false), rows_with(&[], false));
+        assert_eq!(
+            rows_with(&["a", "a/b"], false),
+            [
+                category("a", 0, 3, true),
+                category("a/b", 1, 2, true),
+                category("a/b/c", 2, 1, false),
+                Row::Snippet { hit: 0, depth: 2 },
+                Row::Snippet { hit: 2, depth: 1 },
+                category("ab", 0, 1, false),
+                Row::Snippet { hit: 3, depth: 0 },
+            ]
+        );
+        assert_eq!(
+            rows_with(&[], true),
+            [
+                category("a", 0, 3, true),
+                category("a/b", 1, 2, true),
+                category("a/b/c", 2, 1, true),
+                Row::Snippet { hit: 4, depth: 3 },
+                Row::Snippet { hit: 0, depth: 2 },
+                Row::Snippet { hit: 2, depth: 1 },
+                category("ab", 0, 1, true),
+                Row::Snippet { hit: 1, depth: 1 },
+                Row::Snippet { hit: 3, depth: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn starts_below_the_scope() {
+        let (hits, snippets) = snippets(&[Some("a/b"), Some("a"), Some("a/b/c")]);
+        let expanded = HashSet::from(["a/b".to_string()]);
+        assert_eq!(
+            rows(&hits, &snippets, Some("a/"), &expanded, false),
+            [
+                category("a/b", 0, 2, true),
+                category("a/b/c", 1, 1, false),
+                Row::Snippet { hit: 0, depth: 1 },
+                Row::Snippet { hit: 1, depth: 0 },
+            ]
+        );
+    }
+}
@@ -1,57 +1,331 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs,
+    time::{Duration, Instant},
+};
+
 use anyhow::Result;
-use arboard::Clipboard;
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use crossterm::event::{KeyEvent, MouseButton, MouseEvent, MouseEventKind};
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use ratatui::{layout::Position, widgets::ListState};
 use run_script::{types::ScriptOptions, IoOptions};
+use tokio::sync::oneshot::error::TryRecvError;
+use tui_textarea::{CursorMove, TextArea};
+
+use crate::{
+    event::{Event, EventHandler},
+    tui::model::Snippet,
+};
 
-use crate::{event::Event, tui::model::Snippet};
+use super::{
+    clipboard, dedupe, external,
+    jobs::{Job, Signal},
+    keymap::{Action, Key, Lookup, Mode},
+    model::{
+        self, App, Areas, Dedupe, Fill, FillAction, Hit, JobsPanel, Prompt, PromptAction, Tree,
+    },
+    placeholder::{self, Generated},
+    restore_terminal, runbook,
+    search::{Field, Query, SortOrder},
+    setup_terminal,
+    syntax::{self, Report, Warning},
+    tmux::{self, Target, Tmux},
+    tree::{self, Row},
+};
 
-use super::{model::App, restore_terminal};
+/// Two clicks on the same row within this interval execute it.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// How long a job being restarted has to exit after SIGTERM, before SIGKILL.
+const RESTART_TIMEOUT: Duration = Duration::from_secs(3);
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Msg {
     AppClose,
     SelectNext,
     SelectPrev,
+    Select(usize),
+    /// Scrolls the details pane by a number of lines, up if negative.
+    ScrollDetails(i32),
+    /// Leaves vim normal mode for the search bar, optionally moving its cursor.
+    FocusSearch {
+        column: Option<u16>,
+    },
+    NormalMode,
+    ToggleHelp,
+    ScrollHelpDown,
+    ScrollHelpUp,
+    SearchBar(SearchBarMsg),
     SearchCmd,
+    ToggleSearchMode,
+    CycleSortOrder,
     ExecuteCmd,
+    RunInBackground,
+    /// Executes the selected snippet in a tmux pane.
+    ExecuteIn(Target),
+    ToggleTree,
+    TogglePreview,
+    Expand,
+    Collapse,
     CopyToClipboard,
     RemoveSnippet,
+    ToggleMark,
+    MarkAll,
+    InvertMarks,
     Edit(EditMsg),
+    Prompt(PromptMsg),
+    Fill(FillMsg),
+    Dedupe(DedupeMsg),
+    Jobs(JobsMsg),
+}
+
+/// Readline style editing of the search bar.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SearchBarMsg {
+    Move(CursorMove),
+    DeleteWordBack,
+    DeleteWordForward,
+    DeleteToLineStart,
+    DeleteToLineEnd,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PromptMsg {
+    Open(PromptAction),
+    Cancel,
+    Submit,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FillMsg {
+    /// The input changed.
+    Filter,
+    SelectNext,
+    SelectPrev,
+    Generated(Generated),
+    Cancel,
+    Submit,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DedupeMsg {
+    Open,
+    Close,
+    SelectNext,
+    SelectPrev,
+    Merge,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum JobsMsg {
+    Open,
+    Close,
+    SelectNext,
+    SelectPrev,
+    Signal(Signal),
+    Restart,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum EditMsg {
-    Open { snippet: Snippet },
+    /// Opens the editor on `snippet`, which replaces the one at `index` when
+    /// saved, or is added if there's none.
+    Open {
+        snippet: Box<Snippet>,
+        index: Option<usize>,
+    },
+    /// Opens the snippet in the editor, or the editor's content, in `$VISUAL` or `$EDITOR`.
+    External,
     Cancel,
     Save,
 }
 
 impl<'a> App<'a> {
     pub fn update(&mut self, msg: Msg) {
+        self.status_msg = None;
         if let Err(err) = match msg {
             Msg::SelectNext => self.select_next(),
             Msg::SelectPrev => self.select_previous(),
+            Msg::Select(i) if self.tree.is_some() => {
+                self.select_row(i);
+                Ok(())
+            }
+            Msg::Select(i) => {
+                self.state
+                    .select(Some(i.min(self.hits.len().saturating_sub(1))));
+                self.details_scroll = 0;
+                Ok(())
+            }
+            Msg::ScrollDetails(lines) => {
+                // The view keeps the scroll within the content.
+                match self.jobs_panel.as_mut() {
+                    Some(panel) => {
+                        panel.scroll = panel.scroll.saturating_add_signed(lines as i16);
+                        panel.follow = false;
+                    }
+                    None => {
+                        self.details_scroll =
+                            self.details_scroll.saturating_add_signed(lines as i16);
+                    }
+                }
+                Ok(())
+            }
+            Msg::FocusSearch { column } => {
+                self.normal_mode = false;
+                // The search bar is a single line, so the column is all that's needed.
+                if let Some(column) = column {
+                    self.search_bar.move_cursor(CursorMove::Jump(0, column));
+                }
+                Ok(())
+            }
+            Msg::NormalMode => {
+                self.normal_mode = true;
+                Ok(())
+            }
             Msg::RemoveSnippet => self.remove_snippet(),
             Msg::SearchCmd => self.search_snippet(),
-            Msg::ExecuteCmd => self.execute_cmd().and_then(|_| self.quit()),
-            Msg::Edit(EditMsg::Open { snippet }) => {
+            Msg::ToggleSearchMode => {
+                self.search_mode = self.search_mode.next();
+                self.search_snippet()
+            }
+            Msg::CycleSortOrder => {
+                self.sort_order = self.sort_order.next();
+                if !self.search_bar.is_empty() {
+                    self.status_msg = Some(format!(
+                        "Sorted by {} once the search bar is empty",
+                        self.sort_order.to_string().to_lowercase()
+                    ));
+                }
+                self.search_snippet()
+            }
+            Msg::ExecuteCmd => match self.selected_category() {
+                Some(path) => {
+                    let path = path.to_string();
+                    self.set_scope(Some(path))
+                }
+                None => self.start_fill(FillAction::Execute),
+            },
+            Msg::RunInBackground => self.start_fill(FillAction::Background),
+            Msg::ExecuteIn(target) => self.start_fill(FillAction::Tmux(target)),
+            Msg::ToggleTree => {
+                self.tree = match self.tree {
+                    Some(_) => None,
+                    None => Some(Tree::default()),
+                };
+                self.search_snippet()
+            }
+            Msg::TogglePreview => {
+                self.show_preview = !self.show_preview;
+                Ok(())
+            }
+            Msg::Expand => self.expand(),
+            Msg::Collapse => self.collapse(),
+            Msg::Edit(EditMsg::Open { snippet, index }) => {
                 self.is_editing = true;
+                self.editing = index;
                 self.editor = Some(snippet.to_string().lines().collect());
+                self.editor_top = 0;
+                self.syntax_report = None;
                 Ok(())
             }
-            Msg::Edit(EditMsg::Save) => {
-                let result = self.save_snippet();
-                self.is_editing = false;
-                self.editor = None;
-                result
-            }
+            Msg::Edit(EditMsg::Save) => match self.save_snippet() {
+                // Keep editing, so the warnings can be fixed.
+                Ok(false) => Ok(()),
+                result => {
+                    self.is_editing = false;
+                    self.editor = None;
+                    self.syntax_report = None;
+                    result.map(|_| ())
+                }
+            },
+            Msg::Edit(EditMsg::External) => self.external_edit(),
             Msg::Edit(EditMsg::Cancel) => {
                 self.is_editing = false;
                 self.editor = None;
+                self.syntax_report = None;
+                Ok(())
+            }
+            Msg::ToggleMark => {
+                if let Some(index) = self.selected_hit().map(|hit| hit.index) {
+                    if !self.marked.remove(&index) {
+                        self.marked.insert(index);
+                    }
+                }
+                self.select_next()
+            }
+            Msg::MarkAll => {
+                self.marked.extend(self.hits.iter().map(|hit| hit.index));
+                Ok(())
+            }
+            Msg::InvertMarks => {
+                for hit in &self.hits {
+                    if !self.marked.remove(&hit.index) {
+                        self.marked.insert(hit.index);
+                    }
+                }
+                Ok(())
+            }
+            Msg::Prompt(PromptMsg::Open(action)) => {
+                let mut input = TextArea::default();
+                match action {
+                    PromptAction::Export => {
+                        input.insert_str("chopsticks-export.toml");
+                    }
+                    PromptAction::RenameCategory => match self.selected_category() {
+                        Some(path) => {
+                            input.insert_str(path);
+                        }
+                        // Only a category row of the tree can be renamed.
+                        None => return,
+                    },
+                    _ => {}
+                }
+                self.prompt = Some(Prompt { action, input });
+                Ok(())
+            }
+            Msg::Prompt(PromptMsg::Submit) => self.submit_prompt(),
+            Msg::Prompt(PromptMsg::Cancel) => {
+                self.prompt = None;
+                Ok(())
+            }
+            Msg::ToggleHelp => {
+                self.help = match self.help {
+                    Some(_) => None,
+                    None => Some(self.mode()),
+                };
+                self.help_scroll = 0;
+                Ok(())
+            }
+            Msg::ScrollHelpDown => {
+                self.help_scroll = self.help_scroll.saturating_add(1);
                 Ok(())
             }
+            Msg::ScrollHelpUp => {
+                self.help_scroll = self.help_scroll.saturating_sub(1);
+                Ok(())
+            }
+            Msg::SearchBar(msg) => {
+                let edited = match msg {
+                    SearchBarMsg::Move(cursor_move) => {
+                        self.search_bar.move_cursor(cursor_move);
+                        false
+                    }
+                    SearchBarMsg::DeleteWordBack => self.search_bar.delete_word(),
+                    SearchBarMsg::DeleteWordForward => self.search_bar.delete_next_word(),
+                    SearchBarMsg::DeleteToLineStart => self.search_bar.delete_line_by_head(),
+                    SearchBarMsg::DeleteToLineEnd => self.search_bar.delete_line_by_end(),
+                };
+                if edited {
+                    self.search_snippet()
+                } else {
+                    Ok(())
+                }
+            }
             Msg::CopyToClipboard => self.copy_to_clipboard(),
+            Msg::Fill(msg) => self.update_fill(msg),
+            Msg::Dedupe(msg) => self.update_dedupe(msg),
+            Msg::Jobs(msg) => self.update_jobs(msg),
             Msg::AppClose => self.quit(),
         } {
             self.error_msg = Some(err.to_string());
@@ -60,171 +334,1549 @@ impl<'a> App<'a> {
 
     pub async fn handle_event(&mut self) -> Option<Msg> {
         match self.events.next().await? {
-            Event::Key(key_evt) => {
-                if self.is_editing {
-                    self.handle_edit_event(key_evt)
-                } else {
-                    match (key_evt.code, key_evt.modifiers) {
-                        // Exit application on `ESC` or `Ctrl-C`
-                        (KeyCode::Esc, _)
-                        | (KeyCode::Char('c'), KeyModifiers::CONTROL)
-                        | (KeyCode::Char('C'), KeyModifiers::CONTROL) => Some(Msg::AppClose),
-                        _ => self.handle_key_event(key_evt),
-                    }
-                }
+            Event::Key(key_evt) => self.handle_key(key_evt),
+            Event::Mouse(mouse_evt) => self.handle_mouse_event(mouse_evt),
+            Event::Paste(text) => self.handle_paste(&text),
+            // The next draw lays the screen out for the new size.
+            Event::Resize(..) | Event::FocusGained => None,
+            Event::FocusLost => {
+                // Keys and clicks from before switching away don't add up with later ones.
+                self.pending_keys.clear();
+                self.last_click = None;
+                None
+            }
+            Event::Tick => {
+                self.poll_jobs();
+                self.poll_generator()
             }
+        }
+    }
 
-            Event::Mouse(mouse_evt) => self.handle_mouse_event(mouse_evt),
-            Event::Tick => None,
-        }
-    }
-
-    fn handle_key_event(&mut self, evt: KeyEvent) -> Option<Msg> {
-        match (evt.code, evt.modifiers) {
-            (KeyCode::Up, _) => Some(Msg::SelectPrev),
-            (KeyCode::Down, _) => Some(Msg::SelectNext),
-            (KeyCode::Enter, KeyModifiers::CONTROL) => Some(Msg::CopyToClipboard),
-            (KeyCode::Enter, _) => Some(Msg::ExecuteCmd),
-            (KeyCode::Char('a'), KeyModifiers::CONTROL)
-            | (KeyCode::Char('A'), KeyModifiers::CONTROL) => Some(Msg::Edit(EditMsg::Open {
-                snippet: Snippet::default(),
-            })),
-            (KeyCode::Char('e'), KeyModifiers::CONTROL)
-            | (KeyCode::Char('E'), KeyModifiers::CONTROL) => {
-                let index = self.state.selected().unwrap();
-                Some(Msg::Edit(EditMsg::Open {
-                    snippet: self
-                        .snippets
-                        .get(index)
-                        .unwrap_or(&Snippet::default())
-                        .clone(),
-                }))
-            }
-            (KeyCode::Char('r'), KeyModifiers::CONTROL)
-            | (KeyCode::Char('R'), KeyModifiers::CONTROL) => Some(Msg::RemoveSnippet),
-
-            _ => {
-                self.search_bar.input(evt);
+    /// Inserts pasted text into the text input of the current mode, as text:
+    /// pasted newlines never submit or execute anything. Single-line inputs
+    /// get the lines joined by spaces.
+    pub(super) fn handle_paste(&mut self, text: &str) -> Option<Msg> {
+        self.pending_keys.clear();
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let line = text
+            .split('\n')
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        match self.mode() {
+            Mode::List | Mode::Tree | Mode::Normal => {
+                self.normal_mode = false;
+                self.search_bar.insert_str(line);
                 Some(Msg::SearchCmd)
             }
+            Mode::Editor => {
+                self.editor.as_mut()?.insert_str(text);
+                None
+            }
+            Mode::Prompt => {
+                self.prompt.as_mut()?.input.insert_str(line);
+                None
+            }
+            Mode::Fill => self
+                .fill
+                .as_mut()?
+                .input
+                .insert_str(line)
+                .then_some(Msg::Fill(FillMsg::Filter)),
+            Mode::Dedupe | Mode::Jobs | Mode::Help => None,
         }
     }
 
-    fn handle_edit_event(&mut self, evt: KeyEvent) -> Option<Msg> {
-        match (evt.code, evt.modifiers) {
-            (KeyCode::Char('s'), KeyModifiers::CONTROL)
-            | (KeyCode::Char('S'), KeyModifiers::CONTROL) => Some(Msg::Edit(EditMsg::Save)),
+    /// Routes a key press through the keymap of the current mode. Unbound keys
+    /// go to whatever text input the mode has.
+    pub(super) fn handle_key(&mut self, evt: KeyEvent) -> Option<Msg> {
+        let mode = self.mode();
+        let key = Key::from(evt);
+        // Plain chars only start a binding while there's no query, so they can still be typed.
+        if matches!(mode, Mode::List | Mode::Tree)
+            && key.is_printable()
+            && self.pending_keys.is_empty()
+            && !self.search_bar.is_empty()
+        {
+            return self.handle_unbound_key(mode, evt);
+        }
+
+        self.pending_keys.push(key);
+        let mut lookup = self.keymap.lookup(mode, &self.pending_keys);
+        if lookup == Lookup::Unbound && self.pending_keys.len() > 1 {
+            // An unfinished sequence is dropped, the last key may still be bound.
+            self.pending_keys = vec![key];
+            lookup = self.keymap.lookup(mode, &self.pending_keys);
+        }
 
-            (KeyCode::Char('c'), KeyModifiers::CONTROL)
-            | (KeyCode::Char('C'), KeyModifiers::CONTROL) => Some(Msg::Edit(EditMsg::Cancel)),
+        match lookup {
+            Lookup::Action(action) => {
+                self.pending_keys.clear();
+                self.action_msg(action)
+            }
+            Lookup::Pending => None,
+            Lookup::Unbound => {
+                self.pending_keys.clear();
+                self.handle_unbound_key(mode, evt)
+            }
+        }
+    }
 
-            _ => {
+    fn handle_unbound_key(&mut self, mode: Mode, evt: KeyEvent) -> Option<Msg> {
+        match mode {
+            // Moving the cursor keeps the results and the selection.
+            Mode::List | Mode::Tree => self.search_bar.input(evt).then_some(Msg::SearchCmd),
+            Mode::Editor => {
                 self.editor.as_mut().unwrap().input(evt);
                 None
             }
+            Mode::Prompt => {
+                self.prompt.as_mut().unwrap().input.input(evt);
+                None
+            }
+            Mode::Fill => self
+                .fill
+                .as_mut()
+                .unwrap()
+                .input
+                .input(evt)
+                .then_some(Msg::Fill(FillMsg::Filter)),
+            Mode::Normal | Mode::Dedupe | Mode::Jobs | Mode::Help => None,
         }
     }
 
-    fn handle_mouse_event(&self, evt: MouseEvent) -> Option<Msg> {
+    /// Turns a bound action into the message that carries it out in the current mode.
+    fn action_msg(&self, action: Action) -> Option<Msg> {
+        let msg = match action {
+            Action::Quit => Msg::AppClose,
+            Action::SelectNext if self.mode() == Mode::Fill => Msg::Fill(FillMsg::SelectNext),
+            Action::SelectPrev if self.mode() == Mode::Fill => Msg::Fill(FillMsg::SelectPrev),
+            Action::SelectNext if self.mode() == Mode::Dedupe => Msg::Dedupe(DedupeMsg::SelectNext),
+            Action::SelectPrev if self.mode() == Mode::Dedupe => Msg::Dedupe(DedupeMsg::SelectPrev),
+            Action::SelectNext if self.mode() == Mode::Jobs => Msg::Jobs(JobsMsg::SelectNext),
+            Action::SelectPrev if self.mode() == Mode::Jobs => Msg::Jobs(JobsMsg::SelectPrev),
+            Action::SelectNext => Msg::SelectNext,
+            Action::SelectPrev => Msg::SelectPrev,
+            Action::SelectFirst => Msg::Select(0),
+            Action::SelectLast => Msg::Select(self.rows().0.saturating_sub(1)),
+            Action::PageDown | Action::PageUp => {
+                let (len, selected) = self.rows();
+                let page = (self.areas.list.height as usize).max(1);
+                Msg::Select(match action {
+                    Action::PageDown => (selected + page).min(len.saturating_sub(1)),
+                    _ => selected.saturating_sub(page),
+                })
+            }
+            Action::FocusSearch => Msg::FocusSearch { column: None },
+            Action::NormalMode => Msg::NormalMode,
+            Action::Execute => Msg::ExecuteCmd,
+            Action::Copy => Msg::CopyToClipboard,
+            Action::Add => Msg::Edit(EditMsg::Open {
+                snippet: Box::default(),
+                index: None,
+            }),
+            Action::Edit => Msg::Edit(EditMsg::Open {
+                snippet: Box::new(self.selected_snippet().cloned().unwrap_or_default()),
+                index: self.selected_hit().map(|hit| hit.index),
+            }),
+            Action::ExternalEdit => Msg::Edit(EditMsg::External),
+            Action::Remove => Msg::RemoveSnippet,
+            Action::ToggleSearchMode => Msg::ToggleSearchMode,
+            Action::CycleSortOrder => Msg::CycleSortOrder,
+            Action::ToggleMark => Msg::ToggleMark,
+            Action::MarkAll => Msg::MarkAll,
+            Action::InvertMarks => Msg::InvertMarks,
+            Action::AddTag => Msg::Prompt(PromptMsg::Open(PromptAction::AddTag)),
+            Action::RemoveTag => Msg::Prompt(PromptMsg::Open(PromptAction::RemoveTag)),
+            Action::MoveToCollection => {
+                Msg::Prompt(PromptMsg::Open(PromptAction::MoveToCollection))
+            }
+            Action::Export => Msg::Prompt(PromptMsg::Open(PromptAction::Export)),
+            Action::Dedupe => Msg::Dedupe(DedupeMsg::Open),
+            Action::ToggleTree => Msg::ToggleTree,
+            Action::TogglePreview => Msg::TogglePreview,
+            Action::Expand => Msg::Expand,
+            Action::Collapse => Msg::Collapse,
+            Action::MoveToCategory => Msg::Prompt(PromptMsg::Open(PromptAction::MoveToCategory)),
+            Action::RenameCategory => Msg::Prompt(PromptMsg::Open(PromptAction::RenameCategory)),
+            Action::Merge => Msg::Dedupe(DedupeMsg::Merge),
+            Action::RunInBackground => Msg::RunInBackground,
+            Action::SendToPane => Msg::ExecuteIn(Target::Pane),
+            Action::RunInSplit => Msg::ExecuteIn(Target::Split),
+            Action::RunInWindow => Msg::ExecuteIn(Target::Window),
+            Action::Jobs => Msg::Jobs(JobsMsg::Open),
+            Action::Interrupt => Msg::Jobs(JobsMsg::Signal(Signal::Interrupt)),
+            Action::Terminate => Msg::Jobs(JobsMsg::Signal(Signal::Terminate)),
+            Action::Restart => Msg::Jobs(JobsMsg::Restart),
+            Action::Save => Msg::Edit(EditMsg::Save),
+            Action::Submit if self.mode() == Mode::Fill => Msg::Fill(FillMsg::Submit),
+            Action::Submit => Msg::Prompt(PromptMsg::Submit),
+            Action::Cancel => match self.mode() {
+                Mode::Help => Msg::ToggleHelp,
+                Mode::Prompt => Msg::Prompt(PromptMsg::Cancel),
+                Mode::Fill => Msg::Fill(FillMsg::Cancel),
+                Mode::Dedupe => Msg::Dedupe(DedupeMsg::Close),
+                Mode::Jobs => Msg::Jobs(JobsMsg::Close),
+                Mode::Editor => Msg::Edit(EditMsg::Cancel),
+                Mode::List | Mode::Normal | Mode::Tree => Msg::AppClose,
+            },
+            Action::ScrollDown if self.mode() == Mode::Help => Msg::ScrollHelpDown,
+            Action::ScrollUp if self.mode() == Mode::Help => Msg::ScrollHelpUp,
+            Action::ScrollDown => Msg::ScrollDetails(1),
+            Action::ScrollUp => Msg::ScrollDetails(-1),
+            Action::ScrollPageDown | Action::ScrollPageUp => {
+                // The details pane's border takes two rows.
+                let page = (self.areas.details.height.saturating_sub(2) as i32).max(1);
+                Msg::ScrollDetails(if action == Action::ScrollPageDown {
+                    page
+                } else {
+                    -page
+                })
+            }
+            Action::Help => Msg::ToggleHelp,
+            Action::LineStart => Msg::SearchBar(SearchBarMsg::Move(CursorMove::Head)),
+            Action::LineEnd => Msg::SearchBar(SearchBarMsg::Move(CursorMove::End)),
+            Action::CharBack => Msg::SearchBar(SearchBarMsg::Move(CursorMove::Back)),
+            Action::CharForward => Msg::SearchBar(SearchBarMsg::Move(CursorMove::Forward)),
+            Action::WordBack => Msg::SearchBar(SearchBarMsg::Move(CursorMove::WordBack)),
+            Action::WordForward => Msg::SearchBar(SearchBarMsg::Move(CursorMove::WordForward)),
+            Action::DeleteWordBack => Msg::SearchBar(SearchBarMsg::DeleteWordBack),
+            Action::DeleteWordForward => Msg::SearchBar(SearchBarMsg::DeleteWordForward),
+            Action::DeleteToLineStart => Msg::SearchBar(SearchBarMsg::DeleteToLineStart),
+            Action::DeleteToLineEnd => Msg::SearchBar(SearchBarMsg::DeleteToLineEnd),
+        };
+        Some(msg)
+    }
+
+    fn handle_mouse_event(&mut self, evt: MouseEvent) -> Option<Msg> {
+        let position = Position::new(evt.column, evt.row);
+        let clicked = matches!(evt.kind, MouseEventKind::Down(MouseButton::Left));
+
+        if clicked {
+            let button = self
+                .areas
+                .buttons
+                .iter()
+                .find(|(rect, _)| rect.contains(position))
+                .map(|(_, action)| *action);
+            if let Some(action) = button {
+                return self.action_msg(action);
+            }
+        }
+        if !matches!(self.mode(), Mode::List | Mode::Normal | Mode::Tree) {
+            return None;
+        }
+
+        let Areas {
+            search_bar,
+            list,
+            details,
+            ..
+        } = self.areas;
         match evt.kind {
+            MouseEventKind::ScrollDown if details.contains(position) => Some(Msg::ScrollDetails(1)),
+            MouseEventKind::ScrollUp if details.contains(position) => Some(Msg::ScrollDetails(-1)),
             MouseEventKind::ScrollDown => Some(Msg::SelectNext),
             MouseEventKind::ScrollUp => Some(Msg::SelectPrev),
+            _ if clicked && list.contains(position) => {
+                let (offset, len) = match &self.tree {
+                    Some(tree) => (tree.state.offset(), tree.rows.len()),
+                    None => (self.state.offset(), self.hits.len()),
+                };
+                let row = (evt.row - list.y) as usize + offset;
+                if row >= len {
+                    return None;
+                }
+                let now = Instant::now();
+                let double_click = self.last_click.is_some_and(|(at, last_row)| {
+                    last_row == row && now.duration_since(at) < DOUBLE_CLICK_INTERVAL
+                });
+                if double_click {
+                    self.last_click = None;
+                    Some(Msg::ExecuteCmd)
+                } else {
+                    self.last_click = Some((now, row));
+                    Some(Msg::Select(row))
+                }
+            }
+            _ if clicked && search_bar.contains(position) => Some(Msg::FocusSearch {
+                column: Some(evt.column.saturating_sub(search_bar.x)),
+            }),
             _ => None,
         }
     }
 
+    /// The number of rows in the list or the tree, and the selected one.
+    fn rows(&self) -> (usize, usize) {
+        match &self.tree {
+            Some(tree) => (tree.rows.len(), tree.state.selected().unwrap_or_default()),
+            None => (self.hits.len(), self.state.selected().unwrap_or_default()),
+        }
+    }
+
     fn select_next(&mut self) -> Result<()> {
+        if let Some(tree) = &self.tree {
+            let next = tree.state.selected().map_or(0, |i| i + 1);
+            self.select_row(if next >= tree.rows.len() { 0 } else { next });
+            return Ok(());
+        }
         // This won't panic because 'selected' is initialized to 0 from the beginning.
         let i = self.state.selected().unwrap();
-        let i = if i >= self.snippets.len().saturating_sub(1) {
+        let i = if i >= self.hits.len().saturating_sub(1) {
             0
         } else {
             i + 1
         };
 
         self.state.select(Some(i));
+        self.details_scroll = 0;
         Ok(())
     }
 
     fn select_previous(&mut self) -> Result<()> {
+        if let Some(tree) = &self.tree {
+            let len = tree.rows.len();
+            let prev = match tree.state.selected() {
+                Some(0) | None => len.saturating_sub(1),
+                Some(i) => i - 1,
+            };
+            self.select_row(prev);
+            return Ok(());
+        }
         // This won't panic because 'selected' is initialized to 0 from the beginning.
         let i = self.state.selected().unwrap();
         let i = if i == 0 {
-            self.snippets.len().saturating_sub(1)
+            self.hits.len().saturating_sub(1)
         } else {
             i - 1
         };
         self.state.select(Some(i));
+        self.details_scroll = 0;
         Ok(())
     }
 
-    fn execute_cmd(&mut self) -> Result<()> {
-        let index = self.state.selected().unwrap();
-        if let Some(snippet) = self.snippets.get(index) {
-            let cmd = snippet.cmd.as_str();
-            let mut options = ScriptOptions::new();
-            options.output_redirection = IoOptions::Inherit;
+    fn update_fill(&mut self, msg: FillMsg) -> Result<()> {
+        match msg {
+            FillMsg::Filter => self.filter_choices(),
+            FillMsg::SelectNext => {
+                let fill = self.fill.as_mut().unwrap();
+                let last = fill.matches.len().checked_sub(1);
+                let next = match fill.state.selected() {
+                    Some(i) => last.map(|last| (i + 1).min(last)),
+                    None => last.map(|_| 0),
+                };
+                fill.state.select(next);
+            }
+            FillMsg::SelectPrev => {
+                let fill = self.fill.as_mut().unwrap();
+                // Going up from the first choice selects the typed text.
+                let prev = fill.state.selected().and_then(|i| i.checked_sub(1));
+                fill.state.select(prev);
+            }
+            FillMsg::Generated(Ok(choices)) => {
+                if let Some(fill) = self.fill.as_mut() {
+                    fill.choices = choices;
+                }
+                self.filter_choices();
+            }
+            FillMsg::Generated(Err(err)) => {
+                if let Some(fill) = self.fill.as_mut() {
+                    fill.generator_error = Some(err);
+                }
+            }
+            FillMsg::Cancel => self.fill = None,
+            FillMsg::Submit => return self.submit_fill(),
+        }
+        Ok(())
+    }
+
+    fn update_dedupe(&mut self, msg: DedupeMsg) -> Result<()> {
+        match msg {
+            DedupeMsg::Open => {
+                let mut dedupe = Dedupe {
+                    groups: dedupe::groups(&self.snippets, self.config.dedupe.threshold),
+                    ..Default::default()
+                };
+                if !dedupe.groups.is_empty() {
+                    dedupe.state.select(Some(0));
+                }
+                self.dedupe = Some(dedupe);
+            }
+            DedupeMsg::Close => self.dedupe = None,
+            DedupeMsg::SelectNext | DedupeMsg::SelectPrev => {
+                let dedupe = self.dedupe.as_mut().unwrap();
+                let last = dedupe.groups.len().saturating_sub(1);
+                let selected = dedupe.state.selected().unwrap_or_default();
+                let selected = if msg == DedupeMsg::SelectNext {
+                    (selected + 1).min(last)
+                } else {
+                    selected.saturating_sub(1)
+                };
+                dedupe.state.select(Some(selected));
+            }
+            DedupeMsg::Merge => self.merge_duplicates()?,
+        }
+        Ok(())
+    }
+
+    fn update_jobs(&mut self, msg: JobsMsg) -> Result<()> {
+        if msg == JobsMsg::Open {
+            let mut panel = JobsPanel {
+                follow: true,
+                ..Default::default()
+            };
+            // The latest job is likely the one to look at.
+            panel.state.select(self.jobs.len().checked_sub(1));
+            self.jobs_panel = Some(panel);
+            return Ok(());
+        }
+        // This is safe. The other messages only come from the open panel.
+        let panel = self.jobs_panel.as_mut().unwrap();
+        let selected = panel.state.selected().filter(|&i| i < self.jobs.len());
+        match msg {
+            JobsMsg::Open => {}
+            JobsMsg::Close => self.jobs_panel = None,
+            JobsMsg::SelectNext | JobsMsg::SelectPrev => {
+                let Some(selected) = selected else {
+                    return Ok(());
+                };
+                let selected = if msg == JobsMsg::SelectNext {
+                    (selected + 1).min(self.jobs.len() - 1)
+                } else {
+                    selected.saturating_sub(1)
+                };
+                panel.state.select(Some(selected));
+                panel.follow = true;
+            }
+            JobsMsg::Signal(signal) => {
+                if let Some(i) = selected {
+                    self.jobs[i].signal(signal)?;
+                }
+            }
+            JobsMsg::Restart => {
+                let Some(i) = selected else {
+                    return Ok(());
+                };
+                panel.follow = true;
+                let job = &mut self.jobs[i];
+                if job.restart_by.is_some() {
+                    return Ok(());
+                }
+                if !job.is_running() {
+                    return self.restart_job(i);
+                }
+                // It starts again once it exited, see `poll_jobs`.
+                job.signal(Signal::Terminate)?;
+                job.restart_by = Some(Instant::now() + RESTART_TIMEOUT);
+                self.status_msg = Some(format!("Stopping job {} to restart it", i + 1));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the snippet of the finished job `i` again, in its place.
+    fn restart_job(&mut self, i: usize) -> Result<()> {
+        let old = &self.jobs[i];
+        let job = Job::spawn(
+            old.index,
+            old.snippet.clone(),
+            self.config.jobs.output_lines,
+        )?;
+        old.remove_log();
+        self.status_msg = Some(format!("Restarted job {} (PID {})", i + 1, job.pid));
+        self.jobs[i] = job;
+        Ok(())
+    }
+
+    /// Tells in the status line about jobs that finished since the last call,
+    /// records their exit codes, and restarts the stopped jobs.
+    fn poll_jobs(&mut self) {
+        for i in 0..self.jobs.len() {
+            let job = &mut self.jobs[i];
+            if let Some(deadline) = job.restart_by {
+                if !job.is_running() {
+                    if let Err(err) = self.restart_job(i) {
+                        self.jobs[i].restart_by = None;
+                        self.error_msg = Some(err.to_string());
+                    }
+                } else if Instant::now() >= deadline {
+                    // Killed again after each timeout, until it's gone.
+                    let _ = job.signal(Signal::Kill);
+                    job.restart_by = Some(deadline + RESTART_TIMEOUT);
+                }
+                continue;
+            }
+
+            self.status_msg = match job.take_exit() {
+                Some(Some(code)) => {
+                    if let Some(index) = job.index {
+                        self.snippets[index].last_exit = Some(code);
+                    }
+                    Some(format!("Job {} exited with status code {code}", i + 1))
+                }
+                Some(None) => Some(format!("Job {} was terminated by signal", i + 1)),
+                None => continue,
+            };
+        }
+    }
+
+    /// Replaces the selected group of duplicates with their merge.
+    fn merge_duplicates(&mut self) -> Result<()> {
+        let Some(dedupe) = self.dedupe.as_mut() else {
+            return Ok(());
+        };
+        let Some(selected) = dedupe.state.selected().filter(|&i| i < dedupe.groups.len()) else {
+            return Ok(());
+        };
+        let group = dedupe.groups.remove(selected);
+
+        let mut merged =
+            dedupe::merge(&group.iter().map(|&i| &self.snippets[i]).collect::<Vec<_>>());
+        merged.updated_at = model::now();
+        self.snippets[group[0]] = merged;
+        // Remove from the back so the remaining indices stay valid.
+        for &index in group[1..].iter().rev() {
+            self.snippets.remove(index);
+        }
+        self.forget_snippets(&group[1..]);
+        self.marked.clear();
+        self.refresh_hits()?;
+
+        // Indices after the removed snippets moved, so group again.
+        let groups = dedupe::groups(&self.snippets, self.config.dedupe.threshold);
+        let dedupe = self.dedupe.as_mut().unwrap();
+        dedupe.state.select(if groups.is_empty() {
+            None
+        } else {
+            Some(selected.min(groups.len() - 1))
+        });
+        dedupe.groups = groups;
+        self.status_msg = Some(format!("Merged {} snippets", group.len()));
+        Ok(())
+    }
+
+    /// Fills in the placeholders of the selected snippet before carrying out
+    /// `action`, or carries it out right away if there are none.
+    fn start_fill(&mut self, action: FillAction) -> Result<()> {
+        let Some(index) = self.selected_hit().map(|hit| hit.index) else {
+            return Ok(());
+        };
+        // Expand references before anything else, so errors show up in the TUI.
+        let snippet = self.expanded_snippet(index)?;
+        let names = placeholder::names(&snippet);
+        if names.is_empty() {
+            return self.finish_fill(action, index, snippet);
+        }
+
+        self.fill = Some(Fill {
+            action,
+            index,
+            snippet,
+            names,
+            values: HashMap::new(),
+            current: 0,
+            input: TextArea::default(),
+            choices: Vec::new(),
+            matches: Vec::new(),
+            state: ListState::default(),
+            generator: None,
+            generator_error: None,
+        });
+        self.show_placeholder();
+        Ok(())
+    }
+
+    /// Resets the fill prompt for the current placeholder and starts its generator.
+    fn show_placeholder(&mut self) {
+        // This is safe. It's only called while filling in placeholders.
+        let fill = self.fill.as_mut().unwrap();
+        let name = &fill.names[fill.current];
+        let definition = fill.snippet.placeholders.iter().find(|p| &p.name == name);
+
+        fill.input = TextArea::default();
+        if let Some(default) = definition.and_then(|p| p.default.as_deref()) {
+            fill.input.insert_str(default);
+        }
+        fill.choices.clear();
+        fill.matches.clear();
+        fill.state.select(None);
+        fill.generator_error = None;
+        fill.generator = definition
+            .and_then(|p| p.generator.as_deref())
+            .map(|generator| placeholder::generate(&fill.snippet, generator));
+    }
+
+    /// Narrows the choices down to those matching the input.
+    fn filter_choices(&mut self) {
+        let Some(fill) = self.fill.as_mut() else {
+            return;
+        };
+        let matcher = SkimMatcherV2::default();
+        let input = fill.input.lines()[0].as_str();
+        let mut matches: Vec<(i64, usize, Vec<usize>)> = fill
+            .choices
+            .iter()
+            .enumerate()
+            .filter_map(|(i, choice)| {
+                if input.is_empty() {
+                    return Some((0, i, Vec::new()));
+                }
+                let (score, positions) = matcher.fuzzy_indices(choice, input)?;
+                Some((score, i, positions))
+            })
+            .collect();
+        matches.sort_by_key(|&(score, i, _)| (Reverse(score), i));
+
+        fill.matches = matches.into_iter().map(|(_, i, p)| (i, p)).collect();
+        fill.state.select(if fill.matches.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+    }
+
+    /// Takes the selected choice, or else the typed text, as the value of the
+    /// current placeholder. After the last one, carries out the fill's action.
+    fn submit_fill(&mut self) -> Result<()> {
+        let Some(fill) = self.fill.as_mut() else {
+            return Ok(());
+        };
+        let value = fill.value();
+        fill.values.insert(fill.names[fill.current].clone(), value);
+        fill.current += 1;
+        if fill.current < fill.names.len() {
+            self.show_placeholder();
+            return Ok(());
+        }
+
+        let fill = self.fill.take().unwrap();
+        let snippet = placeholder::fill(&fill.snippet, &fill.values);
+        self.finish_fill(fill.action, fill.index, snippet)
+    }
+
+    /// Executes or copies the snippet at `index`, as filled in into `snippet`.
+    fn finish_fill(&mut self, action: FillAction, index: usize, snippet: Snippet) -> Result<()> {
+        self.snippets[index].mark_used(model::now());
+        match action {
+            // The snippet's own target applies only inside tmux, elsewhere it runs here.
+            FillAction::Execute => match snippet.target.filter(|_| tmux::inside()) {
+                Some(target) => self.execute_in(target, &snippet),
+                None => {
+                    self.snippets[index].last_exit = self.execute_cmd(snippet)?;
+                    self.quit()
+                }
+            },
+            FillAction::Tmux(target) => self.execute_in(target, &snippet),
+            FillAction::Copy => {
+                let backend = clipboard::copy(&snippet.script(), &self.config.clipboard.backends)?;
+                self.status_msg = Some(format!("Copied with {backend}"));
+                Ok(())
+            }
+            FillAction::Background => {
+                let job = Job::spawn(Some(index), snippet, self.config.jobs.output_lines)?;
+                self.status_msg = Some(format!(
+                    "Started job {} (PID {})",
+                    self.jobs.len() + 1,
+                    job.pid
+                ));
+                self.jobs.push(job);
+                Ok(())
+            }
+        }
+    }
+
+    /// Hands a finished generator's output to the fill prompt.
+    fn poll_generator(&mut self) -> Option<Msg> {
+        let receiver = self.fill.as_mut()?.generator.as_mut()?;
+        let generated = match receiver.try_recv() {
+            Ok(generated) => generated,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Closed) => Err("Generator stopped unexpectedly".to_string()),
+        };
+        self.fill.as_mut()?.generator = None;
+        Some(Msg::Fill(FillMsg::Generated(generated)))
+    }
 
+    /// Runs a filled-in snippet in the restored terminal. Returns its exit code,
+    /// or `None` if it was killed by a signal.
+    fn execute_cmd(&mut self, snippet: Snippet) -> Result<Option<i32>> {
+        if snippet.is_runbook() {
             restore_terminal()?;
             self.terminal_restored = true;
             self.events.stop();
+            return runbook::run(&snippet);
+        }
 
-            let status: std::process::ExitStatus =
-                run_script::spawn_script!(cmd, &options)?.wait()?;
+        let mut options = ScriptOptions::new();
+        options.output_redirection = IoOptions::Inherit;
+        options.runner = snippet.shell;
 
-            match status.code() {
-                Some(code) => println!("Exited with status code: {code}"),
-                None => println!("Process terminated by signal"),
-            }
+        restore_terminal()?;
+        self.terminal_restored = true;
+        self.events.stop();
+
+        let status: std::process::ExitStatus =
+            run_script::spawn_script!(&snippet.cmd, &options)?.wait()?;
+
+        match status.code() {
+            Some(code) => println!("Exited with status code: {code}"),
+            None => println!("Process terminated by signal"),
         }
 
+        Ok(status.code())
+    }
+
+    /// Hands the terminal to another program, like the user's editor.
+    fn suspend(&mut self) -> Result<()> {
+        restore_terminal()?;
+        self.events.stop();
         Ok(())
     }
 
-    fn copy_to_clipboard(&self) -> Result<()> {
-        let mut clipboard = Clipboard::new()?;
+    /// Takes the terminal back after `suspend`.
+    fn resume(&mut self) -> Result<()> {
+        setup_terminal()?;
+        self.events = EventHandler::new(16);
+        self.clear_screen = true;
+        Ok(())
+    }
+
+    /// Edits the built-in editor's content, or else the selected snippet, in the
+    /// user's editor. An edited snippet is saved as if from the built-in editor,
+    /// which stays open with the text if it doesn't parse or has warnings.
+    fn external_edit(&mut self) -> Result<()> {
+        let text = match &self.editor {
+            Some(editor) => editor.lines().join("\n"),
+            None => self
+                .selected_snippet()
+                .cloned()
+                .unwrap_or_default()
+                .to_string(),
+        };
+
+        self.suspend()?;
+        let edited = external::edit_snippet(&text);
+        self.resume()?;
+        let edited = edited?;
 
-        let index = self.state.selected().unwrap();
-        if let Some(snippet) = self.snippets.get(index) {
-            clipboard.set_text(snippet.cmd.as_str())?;
+        let was_editing = self.is_editing;
+        if !was_editing {
+            self.is_editing = true;
+            self.editing = self.selected_hit().map(|hit| hit.index);
         }
+        self.editor = Some(edited.lines().collect());
+        self.editor_top = 0;
+        self.syntax_report = None;
 
+        if was_editing || toml::from_str::<Snippet>(&edited).is_err() {
+            return Ok(());
+        }
+        self.update(Msg::Edit(EditMsg::Save));
         Ok(())
     }
 
-    fn search_snippet(&mut self) -> Result<()> {
+    /// Runs a filled-in snippet in a tmux pane instead of chopsticks' terminal, then quits.
+    fn execute_in(&mut self, target: Target, snippet: &Snippet) -> Result<()> {
+        Tmux::default().run(target, snippet)?;
+        self.quit()
+    }
+
+    /// Copies the marked commands, or the selected one after filling in its placeholders.
+    fn copy_to_clipboard(&mut self) -> Result<()> {
+        let targets = self.targets();
+        if targets.len() <= 1 {
+            return self.start_fill(FillAction::Copy);
+        }
+
+        let cmds = targets
+            .iter()
+            .map(|&i| self.expanded_script(i))
+            .collect::<Result<Vec<_>>>()?;
+        let backend = clipboard::copy(&cmds.join("\n"), &self.config.clipboard.backends)?;
+        let now = model::now();
+        for &i in &targets {
+            self.snippets[i].mark_used(now);
+        }
+        self.status_msg = Some(format!("Copied {} commands with {backend}", targets.len()));
+
+        Ok(())
+    }
+
+    /// Rebuilds `hits` from the search bar. Snippets that don't match the query
+    /// are left out; an empty query lists everything by priority.
+    pub(super) fn search_snippet(&mut self) -> Result<()> {
         let matcher = SkimMatcherV2::default();
+        let query = Query::parse(&self.search_bar.lines()[0], self.search_mode)?;
+        self.error_msg = None;
 
-        self.snippets.iter_mut().for_each(|s| {
-            let mut priority = 0i64;
-            self.search_bar.lines()[0]
-                .split_ascii_whitespace()
-                .for_each(|k| {
-                    priority += matcher.fuzzy_match(&s.cmd, k).unwrap_or_default();
-                    priority += matcher.fuzzy_match(&s.description, k).unwrap_or_default();
-                });
-            s.priority = priority;
-        });
+        let weights = &self.config.search.weights;
+        let scope = self.tree.as_ref().and_then(|tree| tree.scope.as_deref());
+
+        self.hits = self
+            .snippets
+            .iter()
+            .enumerate()
+            .filter_map(|(index, s)| {
+                if scope.is_some_and(|scope| !tree::in_scope(s.category.as_deref(), scope)) {
+                    return None;
+                }
+                let tags = s.tags.join(" ");
+                let script = s.script();
+                let fields = [
+                    (Field::Cmd, script.as_ref()),
+                    (Field::Description, s.description.as_str()),
+                    (Field::Tags, tags.as_str()),
+                    (Field::Shell, s.shell.as_deref().unwrap_or_default()),
+                    (Field::Dir, s.dir.as_deref().unwrap_or_default()),
+                ];
+                let (score, positions) = query.matches(&matcher, weights, &fields)?;
+                let mut positions = positions.into_iter();
+                Some(Hit {
+                    index,
+                    score,
+                    cmd: positions.next().unwrap_or_default(),
+                    description: positions.next().unwrap_or_default(),
+                    tags: positions.next().unwrap_or_default(),
+                })
+            })
+            .collect();
 
-        self.snippets.sort_by(|a, b| b.priority.cmp(&a.priority));
+        if query.is_empty() {
+            let snippets = &self.snippets;
+            let key = |h: &Hit| &snippets[h.index];
+            match self.sort_order {
+                SortOrder::Priority => self.hits.sort_by_key(|h| Reverse(key(h).priority)),
+                SortOrder::Alphabetical => self
+                    .hits
+                    .sort_by_cached_key(|h| key(h).script().to_lowercase()),
+                SortOrder::Newest => self.hits.sort_by_key(|h| Reverse(key(h).created_at)),
+                SortOrder::RecentlyUpdated => self.hits.sort_by_key(|h| Reverse(key(h).updated_at)),
+                SortOrder::RecentlyUsed => self.hits.sort_by_key(|h| Reverse(key(h).last_used_at)),
+                SortOrder::MostUsed => self.hits.sort_by_key(|h| Reverse(key(h).use_count)),
+            }
+        } else {
+            self.hits.sort_by_key(|h| std::cmp::Reverse(h.score));
+        }
         self.state.select(Some(0));
+        self.details_scroll = 0;
+        self.rebuild_tree();
 
         Ok(())
     }
 
-    fn save_snippet(&mut self) -> Result<()> {
-        let snippet = self.editor.as_ref().unwrap().lines().join("\n");
-        let snippet: Snippet = toml::from_str(&snippet)?;
-        self.snippets.push(snippet);
-        Ok(())
+    /// Saves the snippet in the editor, unless the syntax check finds problems
+    /// in its commands. Then returns `false` with the warnings in `syntax_report`;
+    /// saving the same content again saves it anyway.
+    fn save_snippet(&mut self) -> Result<bool> {
+        let source = self.editor.as_ref().unwrap().lines().join("\n");
+        let mut snippet: Snippet = toml::from_str(&source)?;
+
+        let confirmed = self
+            .syntax_report
+            .as_ref()
+            .is_some_and(|report| report.source == source);
+        if !confirmed {
+            let mut warnings = syntax::check(&source, &snippet, &self.config.validation);
+            if let Some(i) = dedupe::find_duplicate(&self.snippets, &snippet, self.editing) {
+                let description = self.snippets[i].description.trim();
+                let message = if description.is_empty() {
+                    "Same command as an existing snippet".to_string()
+                } else {
+                    format!("Same command as an existing snippet: {description}")
+                };
+                let line = syntax::cmd_line(&source);
+                warnings.insert(0, Warning { line, message });
+            }
+            if !warnings.is_empty() {
+                self.syntax_report = Some(Report { source, warnings });
+                return Ok(false);
+            }
+        }
+
+        let now = model::now();
+        snippet.updated_at = now;
+        match self.editing {
+            Some(index) => {
+                let old = &self.snippets[index];
+                snippet.id = old.id;
+                snippet.created_at = old.created_at;
+                snippet.last_used_at = old.last_used_at;
+                snippet.last_exit = old.last_exit;
+                self.snippets[index] = snippet;
+            }
+            None => {
+                snippet.id = model::next_id(&self.snippets);
+                snippet.created_at = now;
+                self.snippets.push(snippet);
+            }
+        }
+        self.refresh_hits()?;
+        Ok(true)
     }
 
     fn remove_snippet(&mut self) -> Result<()> {
-        let index = self.state.selected().unwrap();
-        if index < self.snippets.len() {
+        let targets = self.targets();
+        if targets.is_empty() {
+            return Ok(());
+        }
+        if targets.len() > 1 {
+            self.update(Msg::Prompt(PromptMsg::Open(PromptAction::ConfirmRemove)));
+            return Ok(());
+        }
+        self.remove_snippets(&targets)
+    }
+
+    fn remove_snippets(&mut self, targets: &[usize]) -> Result<()> {
+        // Remove from the back so the remaining indices stay valid.
+        for &index in targets.iter().rev() {
             self.snippets.remove(index);
         }
+        self.forget_snippets(targets);
+        self.marked.clear();
+        self.refresh_hits()?;
+        if targets.len() > 1 {
+            self.status_msg = Some(format!("Removed {} snippets", targets.len()));
+        }
+
+        Ok(())
+    }
+
+    fn submit_prompt(&mut self) -> Result<()> {
+        let Some(prompt) = self.prompt.take() else {
+            return Ok(());
+        };
+        let input = prompt.input.lines()[0].trim().to_string();
+        let targets = self.targets();
+        let now = model::now();
+
+        match prompt.action {
+            PromptAction::AddTag | PromptAction::RemoveTag if input.is_empty() => {}
+            PromptAction::AddTag => {
+                for &i in &targets {
+                    let tags = &mut self.snippets[i].tags;
+                    if !tags.contains(&input) {
+                        tags.push(input.clone());
+                        self.snippets[i].updated_at = now;
+                    }
+                }
+                self.status_msg = Some(format!("Tagged {} snippets `{}`", targets.len(), input));
+            }
+            PromptAction::RemoveTag => {
+                for &i in &targets {
+                    let snippet = &mut self.snippets[i];
+                    if snippet.tags.contains(&input) {
+                        snippet.tags.retain(|tag| *tag != input);
+                        snippet.updated_at = now;
+                    }
+                }
+                self.status_msg = Some(format!("Untagged {} snippets `{}`", targets.len(), input));
+            }
+            PromptAction::MoveToCollection => {
+                let collection = Some(input).filter(|c| !c.is_empty());
+                for &i in &targets {
+                    self.snippets[i].collection = collection.clone();
+                    self.snippets[i].updated_at = now;
+                }
+                self.status_msg = Some(match collection {
+                    Some(c) => format!("Moved {} snippets to `{}`", targets.len(), c),
+                    None => format!("Removed {} snippets from their collection", targets.len()),
+                });
+            }
+            PromptAction::MoveToCategory => {
+                let category = Some(tree::parts(&input).join("/")).filter(|c| !c.is_empty());
+                for &i in &targets {
+                    self.snippets[i].category = category.clone();
+                    self.snippets[i].updated_at = now;
+                }
+                self.status_msg = Some(match category {
+                    Some(c) => format!("Moved {} snippets to category `{}`", targets.len(), c),
+                    None => format!("Removed {} snippets from their category", targets.len()),
+                });
+            }
+            PromptAction::RenameCategory => {
+                let Some(from) = self.selected_category().map(String::from) else {
+                    return Ok(());
+                };
+                let mut count = 0;
+                for snippet in &mut self.snippets {
+                    let renamed = snippet
+                        .category
+                        .as_deref()
+                        .and_then(|category| tree::rename(category, &from, &input));
+                    if let Some(renamed) = renamed {
+                        snippet.category = Some(renamed).filter(|c| !c.is_empty());
+                        snippet.updated_at = now;
+                        count += 1;
+                    }
+                }
+                // Keep the renamed categories open, and the scope on the renamed one.
+                let tree = self.tree.as_mut().unwrap();
+                tree.expanded = tree
+                    .expanded
+                    .iter()
+                    .map(|path| tree::rename(path, &from, &input).unwrap_or(path.clone()))
+                    .collect();
+                if let Some(scope) = &tree.scope {
+                    if let Some(renamed) = tree::rename(scope, &from, &input) {
+                        tree.scope = Some(renamed).filter(|c| !c.is_empty());
+                    }
+                }
+                self.status_msg = Some(format!("Renamed `{}` in {} snippets", from, count));
+            }
+            PromptAction::ConfirmRemove => {
+                if input.to_lowercase().starts_with('y') {
+                    return self.remove_snippets(&targets);
+                }
+            }
+            PromptAction::Export => {
+                let snippets: Vec<&Snippet> = targets.iter().map(|&i| &self.snippets[i]).collect();
+                let content = toml::to_string_pretty(&HashMap::from([("snippets", &snippets)]))?;
+                fs::write(&input, content)?;
+                self.status_msg = Some(format!("Exported {} snippets to {}", targets.len(), input));
+            }
+        }
+
+        self.refresh_hits()
+    }
+
+    /// Lays out the tree again after the hits or the expanded categories
+    /// changed, keeping the cursor on the same category or snippet.
+    fn rebuild_tree(&mut self) {
+        let Some(tree) = self.tree.as_mut() else {
+            return;
+        };
+        let selected = tree
+            .state
+            .selected()
+            .and_then(|i| tree.rows.get(i))
+            .map(|row| match row {
+                Row::Category { path, .. } => Err(path.clone()),
+                Row::Snippet { hit, .. } => Ok(self.hits.get(*hit).map(|hit| hit.index)),
+            });
+
+        let expand_all = !self.search_bar.is_empty();
+        tree.rows = tree::rows(
+            &self.hits,
+            &self.snippets,
+            tree.scope.as_deref(),
+            &tree.expanded,
+            expand_all,
+        );
+        let row = selected.and_then(|selected| {
+            tree.rows.iter().position(|row| match (row, &selected) {
+                (Row::Category { path, .. }, Err(selected)) => path == selected,
+                (Row::Snippet { hit, .. }, Ok(selected)) => {
+                    Some(self.hits[*hit].index) == *selected
+                }
+                _ => false,
+            })
+        });
+        self.select_row(row.unwrap_or_default());
+    }
+
+    /// Moves the tree cursor, and the list selection with it when it lands on a snippet.
+    fn select_row(&mut self, row: usize) {
+        let Some(tree) = self.tree.as_mut() else {
+            return;
+        };
+        let row = row.min(tree.rows.len().saturating_sub(1));
+        tree.state.select((!tree.rows.is_empty()).then_some(row));
+        let hit = match tree.rows.get(row) {
+            Some(Row::Snippet { hit, .. }) => Some(*hit),
+            _ => None,
+        };
+        self.state.select(hit);
+        self.details_scroll = 0;
+    }
+
+    /// Limits the tree and the search to a category, or lifts the limit.
+    fn set_scope(&mut self, scope: Option<String>) -> Result<()> {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.scope = scope;
+            tree.state.select(None);
+        }
+        self.search_snippet()
+    }
 
+    /// Expands the selected category, or steps into it if it is expanded.
+    fn expand(&mut self) -> Result<()> {
+        let Some(tree) = self.tree.as_mut() else {
+            return Ok(());
+        };
+        let Some(row) = tree.state.selected() else {
+            return Ok(());
+        };
+        if let Some(Row::Category { path, expanded, .. }) = tree.rows.get(row) {
+            if *expanded {
+                self.select_row(row + 1);
+            } else {
+                tree.expanded.insert(path.clone());
+                self.rebuild_tree();
+            }
+        }
         Ok(())
     }
+
+    /// Collapses the selected category, or else moves to the parent category.
+    /// At the top of a scoped tree, widens the scope by a level.
+    fn collapse(&mut self) -> Result<()> {
+        let Some(tree) = self.tree.as_mut() else {
+            return Ok(());
+        };
+        let Some(row) = tree.state.selected() else {
+            let parent = tree.scope.as_deref().and_then(tree::parent);
+            return self.set_scope(parent);
+        };
+        if let Some(Row::Category {
+            path,
+            expanded: true,
+            ..
+        }) = tree.rows.get(row)
+        {
+            if !self.search_bar.is_empty() {
+                // Everything is expanded while searching.
+                return Ok(());
+            }
+            tree.expanded.remove(path);
+            self.rebuild_tree();
+            return Ok(());
+        }
+
+        let depth = tree.rows[row].depth();
+        if depth == 0 {
+            if let Some(scope) = tree.scope.clone() {
+                let parent = tree::parent(&scope);
+                self.set_scope(parent)?;
+                // Land on the category that was the scope.
+                if let Some(tree) = self.tree.as_mut() {
+                    if let Some(row) = tree
+                        .rows
+                        .iter()
+                        .position(|row| matches!(row, Row::Category { path, .. } if *path == scope))
+                    {
+                        self.select_row(row);
+                    }
+                }
+            }
+            return Ok(());
+        }
+        if let Some(parent) = tree.rows[..row].iter().rposition(|r| r.depth() < depth) {
+            self.select_row(parent);
+        }
+        Ok(())
+    }
+
+    /// Re-runs the search after snippets changed, keeping the cursor in place.
+    fn refresh_hits(&mut self) -> Result<()> {
+        if self.tree.is_some() {
+            // The tree keeps its cursor by itself.
+            return self.search_snippet();
+        }
+        let selected = self.state.selected();
+        self.search_snippet()?;
+        self.state
+            .select(selected.map(|i| i.min(self.hits.len().saturating_sub(1))));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    use super::*;
+    use crate::{
+        config::KeymapPreset,
+        tui::{keymap::Keymap, model::Step},
+    };
+
+    #[tokio::test]
+    async fn paste_into_the_search_bar_never_executes() {
+        let mut app = App::new();
+        app.snippets = vec![Snippet {
+            cmd: "git status".into(),
+            ..Default::default()
+        }];
+        app.search_snippet().unwrap();
+
+        let msg = app.handle_paste("git\r\nstatus\n");
+        assert!(matches!(msg, Some(Msg::SearchCmd)));
+        assert_eq!(app.search_bar.lines(), ["git status"]);
+    }
+
+    #[tokio::test]
+    async fn paste_into_the_editor_keeps_lines() {
+        let mut app = App::new();
+        app.update(Msg::Edit(EditMsg::Open {
+            snippet: Box::default(),
+            index: None,
+        }));
+        let editor = app.editor.as_mut().unwrap();
+        editor.select_all();
+        editor.cut();
+
+        assert!(app
+            .handle_paste("for i in 1 2; do\r\n  echo $i\r\ndone")
+            .is_none());
+        assert_eq!(
+            app.editor.as_ref().unwrap().lines(),
+            ["for i in 1 2; do", "  echo $i", "done"]
+        );
+    }
+
+    fn snippet(cmd: &str) -> Snippet {
+        Snippet {
+            cmd: cmd.into(),
+            ..Default::default()
+        }
+    }
+
+    /// An app with `cmds` as its snippets, and without validation commands to run.
+    fn app_with(cmds: &[&str]) -> App<'static> {
+        let mut app = App::new();
+        app.config.validation.syntax = false;
+        app.config.validation.shellcheck = false;
+        app.snippets = cmds.iter().map(|cmd| snippet(cmd)).collect();
+        app.search_snippet().unwrap();
+        app
+    }
+
+    fn press(app: &mut App, keys: &[(KeyCode, KeyModifiers)]) -> Option<Msg> {
+        keys.iter()
+            .map(|&(code, modifiers)| app.handle_key(KeyEvent::new(code, modifiers)))
+            .last()
+            .flatten()
+    }
+
+    #[tokio::test]
+    async fn unbound_keys_go_to_the_search_bar() {
+        let ctrl_x = (KeyCode::Char('x'), KeyModifiers::CONTROL);
+        let z = (KeyCode::Char('z'), KeyModifiers::NONE);
+        let mut app = app_with(&["ls"]);
+        app.keymap = Keymap::preset(KeymapPreset::Readline);
+        assert!(press(&mut app, &[ctrl_x]).is_none());
+        assert_eq!(app.pending_keys.len(), 1);
+        // An unfinished sequence is dropped and the last key typed.
+        assert!(matches!(press(&mut app, &[z]), Some(Msg::SearchCmd)));
+        assert!(app.pending_keys.is_empty());
+        assert_eq!(app.search_bar.lines(), ["z"]);
+
+        // Vim's normal mode drops them instead.
+        let mut app = app_with(&["ls"]);
+        app.keymap = Keymap::preset(KeymapPreset::Vim);
+        app.normal_mode = true;
+        let g = (KeyCode::Char('g'), KeyModifiers::NONE);
+        assert!(press(&mut app, &[g, z]).is_none());
+        assert!(app.pending_keys.is_empty());
+        assert!(app.search_bar.is_empty());
+        assert!(matches!(press(&mut app, &[g, g]), Some(Msg::Select(0))));
+    }
+
+    #[tokio::test]
+    async fn sort_orders_apply_without_a_query() {
+        let mut app = app_with(&["b", "A", "c"]);
+        // priority, created_at, updated_at, last_used_at (0 if never), use_count
+        for (snippet, times) in
+            app.snippets
+                .iter_mut()
+                .zip([[1, 3, 5, 0, 2], [3, 1, 6, 9, 0], [2, 2, 4, 7, 5]])
+        {
+            snippet.priority = times[0] as i64;
+            snippet.created_at = times[1];
+            snippet.updated_at = times[2];
+            snippet.last_used_at = (times[3] > 0).then_some(times[3]);
+            snippet.use_count = times[4];
+        }
+        let listed = |app: &App| -> Vec<String> {
+            app.hits
+                .iter()
+                .map(|hit| app.snippets[hit.index].cmd.clone())
+                .collect()
+        };
+
+        let mut order = SortOrder::default();
+        for expected in [
+            ["A", "c", "b"],
+            ["A", "b", "c"],
+            ["b", "c", "A"],
+            ["A", "b", "c"],
+            ["A", "c", "b"],
+            ["c", "b", "A"],
+        ] {
+            app.sort_order = order;
+            app.search_snippet().unwrap();
+            assert_eq!(listed(&app), expected, "{order}");
+            order = order.next();
+        }
+        assert_eq!(order, SortOrder::default());
+
+        // Search results go by score whatever the order.
+        app.search_bar.insert_str("c");
+        app.search_snippet().unwrap();
+        assert_eq!(listed(&app), ["c"]);
+    }
+
+    #[tokio::test]
+    async fn question_mark_starts_a_query() {
+        let mut app = app_with(&["ls"]);
+        let msg = app.handle_key(KeyEvent::from(KeyCode::Char('?')));
+        assert!(matches!(msg, Some(Msg::SearchCmd)));
+        assert_eq!(app.search_bar.lines(), ["?"]);
+        assert!(app.help.is_none());
+    }
+
+    /// Fills in the placeholders of the selected snippet with `values`,
+    /// checking before the last one that the preview is what the fill produces.
+    fn fill_previewed(app: &mut App, values: &[&str]) {
+        for (i, value) in values.iter().enumerate() {
+            let fill = app.fill.as_mut().unwrap();
+            fill.input = TextArea::from([*value]);
+            if i == values.len() - 1 {
+                let mut values = fill.values.clone();
+                values.insert(fill.names[fill.current].clone(), value.to_string());
+                let filled = placeholder::fill(&fill.snippet, &values);
+                assert_eq!(fill.preview().0, filled.script());
+            }
+            app.update(Msg::Fill(FillMsg::Submit));
+        }
+    }
+
+    #[tokio::test]
+    async fn preview_is_what_runs() {
+        let mut app = app_with(&[r#"git commit -m "<msg>" --author <who>"#]);
+        app.start_fill(FillAction::Background).unwrap();
+        let (preview, positions) = {
+            app.fill.as_mut().unwrap().input = TextArea::from(["fix $x"]);
+            app.fill.as_ref().unwrap().preview()
+        };
+        assert_eq!(preview, r#"git commit -m "fix \$x" --author <who>"#);
+        assert_eq!(positions, (15..22).collect::<Vec<_>>());
+
+        fill_previewed(&mut app, &["fix $x", "A B"]);
+        assert_eq!(
+            app.jobs[0].snippet.cmd,
+            r#"git commit -m "fix \$x" --author 'A B'"#
+        );
+        while app.jobs[0].is_running() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        app.jobs[0].remove_log();
+    }
+
+    #[tokio::test]
+    async fn runbook_preview_is_what_runs() {
+        let mut app = app_with(&[]);
+        app.snippets = vec![Snippet {
+            steps: ["echo 'unbalanced", "echo <msg>"]
+                .map(|cmd| Step {
+                    cmd: cmd.into(),
+                    description: String::new(),
+                })
+                .to_vec(),
+            ..Default::default()
+        }];
+        app.search_snippet().unwrap();
+        // Refused for a runbook, after filling in.
+        app.start_fill(FillAction::Tmux(Target::Split)).unwrap();
+        app.fill.as_mut().unwrap().input = TextArea::from(["a b"]);
+        let (preview, positions) = app.fill.as_ref().unwrap().preview();
+        // The quote in the first step doesn't reach into the second.
+        assert_eq!(preview, "echo 'unbalanced\necho 'a b'");
+        assert_eq!(positions, (22..27).collect::<Vec<_>>());
+        fill_previewed(&mut app, &["a b"]);
+    }
+
+    #[tokio::test]
+    async fn saving_an_edit_replaces_the_snippet() {
+        let mut app = app_with(&["ls", "pwd"]);
+        app.snippets[1].created_at = 1;
+        app.snippets[1].use_count = 3;
+        let mut edited = app.snippets[1].clone();
+        edited.cmd = "pwd -P".into();
+
+        app.update(Msg::Edit(EditMsg::Open {
+            snippet: Box::new(edited),
+            index: Some(1),
+        }));
+        app.update(Msg::Edit(EditMsg::Save));
+        assert!(app.editor.is_none());
+        let cmds: Vec<&str> = app.snippets.iter().map(|s| s.cmd.as_str()).collect();
+        assert_eq!(cmds, ["ls", "pwd -P"]);
+        assert_eq!(app.snippets[1].created_at, 1);
+        assert_eq!(app.snippets[1].use_count, 3);
+    }
+
+    #[tokio::test]
+    async fn adding_a_duplicate_warns_first() {
+        let mut app = app_with(&["git  status"]);
+        app.snippets[0].description = "Status".into();
+        app.update(Msg::Edit(EditMsg::Open {
+            snippet: Box::new(snippet("git status")),
+            index: None,
+        }));
+
+        app.update(Msg::Edit(EditMsg::Save));
+        let report = app.syntax_report.as_ref().unwrap();
+        assert_eq!(
+            report.warnings[0].message,
+            "Same command as an existing snippet: Status"
+        );
+        assert_eq!(app.snippets.len(), 1);
+
+        // Saving again keeps it anyway.
+        app.update(Msg::Edit(EditMsg::Save));
+        assert!(app.editor.is_none());
+        assert_eq!(app.snippets.len(), 2);
+    }
+
+    /// Polls the jobs until `done` holds, like the ticks do.
+    async fn poll_until(app: &mut App<'_>, done: impl Fn(&App) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(app) {
+            assert!(Instant::now() < deadline, "timed out");
+            app.poll_jobs();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn jobs_record_the_exit_code() {
+        let mut app = app_with(&["ls", "exit 4"]);
+        app.jobs
+            .push(Job::spawn(Some(1), app.snippets[1].clone(), 10).unwrap());
+        poll_until(&mut app, |app| app.snippets[1].last_exit.is_some()).await;
+        assert_eq!(app.snippets[1].last_exit, Some(4));
+        assert_eq!(
+            app.status_msg.as_deref(),
+            Some("Job 1 exited with status code 4")
+        );
+        app.jobs[0].remove_log();
+
+        // Removing a snippet before it moves the job along.
+        app.jobs[0].index = Some(1);
+        app.forget_snippets(&[0]);
+        assert_eq!(app.jobs[0].index, Some(0));
+        app.forget_snippets(&[0]);
+        assert_eq!(app.jobs[0].index, None);
+    }
+
+    #[tokio::test]
+    async fn restart_waits_for_the_job_to_exit() {
+        // Ignores SIGTERM, so it only stops once killed.
+        let mut app = app_with(&["trap '' TERM; echo started; while :; do sleep 0.1; done"]);
+        app.jobs
+            .push(Job::spawn(Some(0), app.snippets[0].clone(), 10).unwrap());
+        let pid = app.jobs[0].pid;
+        poll_until(&mut app, |app| !app.jobs[0].output().is_empty()).await;
+        app.update(Msg::Jobs(JobsMsg::Open));
+
+        app.update(Msg::Jobs(JobsMsg::Restart));
+        assert!(app.jobs[0].restart_by.is_some());
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        app.poll_jobs();
+        assert_eq!(app.jobs[0].pid, pid);
+        assert!(app.jobs[0].is_running());
+
+        // Past the timeout, it's killed and then started again.
+        app.jobs[0].restart_by = Some(Instant::now());
+        poll_until(&mut app, |app| app.jobs[0].pid != pid).await;
+        assert!(app.jobs[0].restart_by.is_none());
+        assert_eq!(app.snippets[0].last_exit, None);
+
+        app.jobs[0].signal(Signal::Kill).unwrap();
+        poll_until(&mut app, |app| !app.jobs[0].is_running()).await;
+        app.jobs[0].remove_log();
+    }
+
+    #[tokio::test]
+    async fn signals_reach_the_job() {
+        let mut app = app_with(&[
+            "trap 'echo INT; exit 5' INT; trap 'echo TERM; exit 6' TERM; \
+             echo started; while :; do sleep 0.1; done",
+        ]);
+        for (signal, name, code) in [
+            (Signal::Interrupt, "INT", 5),
+            (Signal::Terminate, "TERM", 6),
+        ] {
+            app.jobs
+                .push(Job::spawn(Some(0), app.snippets[0].clone(), 10).unwrap());
+            poll_until(&mut app, |app| !app.jobs[0].output().is_empty()).await;
+            app.update(Msg::Jobs(JobsMsg::Open));
+
+            app.update(Msg::Jobs(JobsMsg::Signal(signal)));
+            poll_until(&mut app, |app| app.snippets[0].last_exit.is_some()).await;
+            // The shell may also tell that `sleep` was killed.
+            assert_eq!(app.jobs[0].output().last().unwrap(), name);
+            assert_eq!(app.snippets[0].last_exit, Some(code));
+
+            // A finished job has nothing to signal.
+            app.update(Msg::Jobs(JobsMsg::Signal(signal)));
+            assert_eq!(
+                app.error_msg.take().as_deref(),
+                Some("The job isn't running")
+            );
+            app.jobs.remove(0).remove_log();
+            app.snippets[0].last_exit = None;
+        }
+    }
+
+    #[tokio::test]
+    async fn restart_runs_the_job_again() {
+        let mut app = app_with(&["echo started; exec sleep 10"]);
+        app.jobs
+            .push(Job::spawn(Some(0), app.snippets[0].clone(), 10).unwrap());
+        let pid = app.jobs[0].pid;
+        poll_until(&mut app, |app| !app.jobs[0].output().is_empty()).await;
+        app.update(Msg::Jobs(JobsMsg::Open));
+
+        // Stopped with SIGTERM, then started again once it exited.
+        app.update(Msg::Jobs(JobsMsg::Restart));
+        poll_until(&mut app, |app| app.jobs[0].pid != pid).await;
+        assert!(app.jobs[0].restart_by.is_none());
+        assert_eq!(app.jobs.len(), 1);
+        poll_until(&mut app, |app| !app.jobs[0].output().is_empty()).await;
+        assert_eq!(app.jobs[0].output(), ["started"]);
+
+        // A finished job starts again right away.
+        app.jobs[0].signal(Signal::Kill).unwrap();
+        poll_until(&mut app, |app| !app.jobs[0].is_running()).await;
+        let pid = app.jobs[0].pid;
+        app.update(Msg::Jobs(JobsMsg::Restart));
+        assert_ne!(app.jobs[0].pid, pid);
+        assert!(app.jobs[0].is_running());
+
+        app.jobs[0].signal(Signal::Kill).unwrap();
+        poll_until(&mut app, |app| !app.jobs[0].is_running()).await;
+        app.jobs[0].remove_log();
+    }
+
+    fn answer(app: &mut App, input: &str) {
+        app.prompt.as_mut().unwrap().input.insert_str(input);
+        app.update(Msg::Prompt(PromptMsg::Submit));
+    }
+
+    #[tokio::test]
+    async fn bulk_remove_asks_first() {
+        let mut app = app_with(&["ls", "pwd", "whoami"]);
+        app.marked.extend([0, 2]);
+
+        app.update(Msg::RemoveSnippet);
+        assert_eq!(
+            app.prompt.as_ref().unwrap().action,
+            PromptAction::ConfirmRemove
+        );
+        answer(&mut app, "n");
+        assert_eq!(app.snippets.len(), 3);
+
+        app.update(Msg::RemoveSnippet);
+        answer(&mut app, "y");
+        let left: Vec<&str> = app.snippets.iter().map(|s| s.cmd.as_str()).collect();
+        assert_eq!(left, ["pwd"]);
+        assert!(app.marked.is_empty());
+
+        // A single snippet goes without asking.
+        app.update(Msg::RemoveSnippet);
+        assert!(app.prompt.is_none());
+        assert!(app.snippets.is_empty());
+    }
 }
@@ -1,75 +1,172 @@
-use super::model::App;
+use super::{
+    dedupe,
+    highlight::{emphasize, highlight},
+    keymap::{Action, Binding, Hint, Mode},
+    model::{self, App, Areas, PromptAction},
+    reference,
+    tree::{self, Row},
+};
+use crate::config::{Column, KeymapPreset, ListLayout};
 use ratatui::{
-    layout::{Alignment, Constraint, Layout, Rect},
+    layout::{Alignment, Constraint, Layout, Margin, Rect},
     style::{Style, Stylize},
     text::{Line, Span, Text},
-    widgets::{Block, BorderType, HighlightSpacing, List, ListItem, Padding, Paragraph, Wrap},
+    widgets::{
+        Block, BorderType, Cell, Clear, HighlightSpacing, List, ListItem, Padding, Paragraph,
+        Row as TableRow, Scrollbar, ScrollbarOrientation, ScrollbarState, Table, TableState, Wrap,
+    },
     Frame,
 };
+use unicode_width::UnicodeWidthChar;
+
+/// Below this size, only a notice is shown.
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 10;
 
 impl<'a> App<'a> {
     pub fn view(&mut self, frame: &mut Frame) {
-        let chunks =
-            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).split(frame.size());
+        self.areas = Areas::default();
+        let size = frame.size();
+        if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+            self.view_too_small(frame, size);
+            return;
+        }
+        let chunks = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).split(size);
 
         if self.is_editing {
-            self.view_editor(frame, frame.size());
+            self.view_editor(frame, size);
+        } else if self.dedupe.is_some() {
+            self.view_dedupe(frame, chunks[0]);
+        } else if self.jobs_panel.is_some() {
+            self.view_jobs(frame, chunks[0]);
         } else {
-            let chunks =
-                Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
-                    .split(chunks[0]);
-
+            let (left, preview) = self.panes(chunks[0]);
             {
                 let chunks =
-                    Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).split(chunks[0]);
+                    Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).split(left);
                 self.view_search_bar(frame, chunks[0]);
-                self.view_snippets_list(frame, chunks[1]);
+                if self.tree.is_some() {
+                    self.view_tree(frame, chunks[1]);
+                } else if self.config.list.layout == ListLayout::Table && !self.snippets.is_empty()
+                {
+                    self.view_snippets_table(frame, chunks[1]);
+                } else {
+                    self.view_snippets_list(frame, chunks[1]);
+                }
             }
 
-            self.view_snippet_details(frame, chunks[1]);
+            if self.fill.is_some() {
+                // The preview takes the place of the details, next to the fill prompt.
+                if let Some(preview) = preview {
+                    self.view_fill_preview(frame, preview);
+                }
+                self.view_fill(frame, left);
+            } else if let Some(preview) = preview {
+                self.view_snippet_details(frame, preview);
+            }
         }
 
         if self.error_msg.is_some() {
             self.view_error_msg(frame, chunks[1]);
+        } else if self.status_msg.is_some() {
+            self.view_status_msg(frame, chunks[1]);
         } else {
             self.view_instructions(frame, chunks[1]);
         }
+
+        if self.prompt.is_some() {
+            self.view_prompt(frame, frame.size());
+        }
+        if self.help.is_some() {
+            self.view_help(frame, frame.size());
+        }
+    }
+
+    /// Whether the panes go one below the other rather than side by side.
+    fn is_stacked(&self, width: u16) -> bool {
+        width < self.config.layout.stack_below
+    }
+
+    /// Splits `rect` into the search bar and list, and the preview pane if shown.
+    fn panes(&self, rect: Rect) -> (Rect, Option<Rect>) {
+        if !self.show_preview {
+            return (rect, None);
+        }
+        let split = self.config.layout.split.clamp(10, 90);
+        let constraints = [Constraint::Percentage(split), Constraint::Fill(1)];
+        let [left, preview] = if self.is_stacked(rect.width) {
+            Layout::vertical(constraints).areas(rect)
+        } else {
+            Layout::horizontal(constraints).areas(rect)
+        };
+        (left, Some(preview))
+    }
+
+    fn view_too_small(&mut self, frame: &mut Frame, rect: Rect) {
+        let text = Text::from(vec![
+            Line::from("Terminal too small").bold().red(),
+            Line::from(format!("{}×{}", rect.width, rect.height)),
+            Line::from(format!("Needs at least {}×{}", MIN_WIDTH, MIN_HEIGHT)).dark_gray(),
+        ]);
+        let [_, area, _] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(text.height() as u16),
+            Constraint::Fill(1),
+        ])
+        .areas(rect);
+        frame.render_widget(
+            Paragraph::new(text).centered().wrap(Wrap { trim: true }),
+            area,
+        );
     }
 
     fn view_search_bar(&mut self, frame: &mut Frame, rect: Rect) {
         let block = Block::bordered()
+            .title(format!(" {} ", self.search_mode))
             .border_type(BorderType::Rounded)
             .padding(Padding::horizontal(2));
         let inner = block.inner(rect);
 
+        self.areas.search_bar = inner;
         let search_bar = &mut self.search_bar;
         search_bar.set_placeholder_text("Type to search");
 
         frame.render_widget(block, rect);
-        frame.render_widget(search_bar.widget(), inner);
+        frame.render_widget(&*search_bar, inner);
     }
 
     fn view_snippets_list(&mut self, frame: &mut Frame, rect: Rect) {
-        let block = Block::bordered().border_type(BorderType::Rounded);
+        let mut block = Block::bordered().border_type(BorderType::Rounded);
+        if !self.marked.is_empty() {
+            block = block.title(format!(" {} marked ", self.marked.len()));
+        }
+        let block = self.sort_title(block);
         let inner = block.inner(rect);
+        self.areas.list = inner;
 
         frame.render_widget(block, rect);
 
         if self.snippets.is_empty() {
+            let add = self
+                .keymap
+                .bindings(self.mode())
+                .iter()
+                .find(|b| b.action == Action::Add)
+                .map_or_else(|| "Ctrl-a".to_string(), |b| b.labels()[0].clone());
             let nothing = Paragraph::new(
-                Line::from("Empty ＞︿＜. Press `Ctrl-A` to add a new snippet ヾ(•ω•`)o").bold(),
+                Line::from(format!(
+                    "Empty ＞︿＜. Press `{}` to add a new snippet ヾ(•ω•`)o",
+                    add
+                ))
+                .bold(),
             )
             .centered()
             .wrap(Wrap { trim: true });
             frame.render_widget(nothing, inner);
         } else {
-            let items: Vec<ListItem> = self
-                .snippets
-                .iter()
-                .enumerate()
-                .map(|(index, snippet)| {
-                    let line = Line::from(format!("{:02} {}", index, snippet.cmd));
-                    ListItem::new(line)
+            let items: Vec<ListItem> = (0..self.hits.len())
+                .map(|hit| {
+                    ListItem::new(self.snippet_line(hit, Span::from(format!("{:02} ", hit))))
                 })
                 .collect();
 
@@ -79,7 +176,273 @@ impl<'a> App<'a> {
                 .highlight_style(Style::new().cyan().italic().bold());
 
             frame.render_stateful_widget(list, inner, &mut self.state);
+            render_scrollbar(
+                frame,
+                inner_rows(rect),
+                self.hits.len(),
+                self.state.offset(),
+            );
+        }
+    }
+
+    fn view_snippets_table(&mut self, frame: &mut Frame, rect: Rect) {
+        let mut block = Block::bordered().border_type(BorderType::Rounded);
+        if !self.marked.is_empty() {
+            block = block.title(format!(" {} marked ", self.marked.len()));
+        }
+        let block = self.sort_title(block);
+        let inner = block.inner(rect);
+        // Rows start below the header.
+        self.areas.list = Rect {
+            y: inner.y + 1,
+            height: inner.height.saturating_sub(1),
+            ..inner
+        };
+
+        let columns = &self.config.list.columns;
+        let rows: Vec<Vec<Line>> = (0..self.hits.len())
+            .map(|hit| {
+                columns
+                    .iter()
+                    .map(|&column| self.table_cell(hit, column))
+                    .collect()
+            })
+            .collect();
+
+        // Short columns get their full width, the others share the rest.
+        let naturals: Vec<u16> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, &column)| {
+                rows.iter()
+                    .map(|row| row[i].width())
+                    .chain([column_title(column).len()])
+                    .max()
+                    .unwrap_or_default() as u16
+            })
+            .collect();
+        let flexible: Vec<bool> = columns
+            .iter()
+            .map(|column| {
+                matches!(
+                    column,
+                    Column::Cmd | Column::Description | Column::Tags | Column::Collection
+                )
+            })
+            .collect();
+        // The highlight symbol, the mark, and a space between columns.
+        let spacing = 4 + columns.len().saturating_sub(1) as u16;
+        let widths = fit(&naturals, &flexible, inner.width.saturating_sub(spacing));
+
+        let rows = rows.into_iter().enumerate().map(|(hit, cells)| {
+            let mark = if self.marked.contains(&self.hits[hit].index) {
+                Span::from("●").magenta()
+            } else {
+                Span::from(" ")
+            };
+            let cells = cells
+                .into_iter()
+                .zip(&widths)
+                .map(|(line, &width)| Cell::from(truncate(line, width as usize)));
+            TableRow::new([Cell::from(mark)].into_iter().chain(cells))
+        });
+        let header = TableRow::new(
+            [Cell::from("")].into_iter().chain(
+                columns
+                    .iter()
+                    .map(|&column| Cell::from(column_title(column))),
+            ),
+        )
+        .style(Style::new().cyan().bold());
+        let table = Table::new(
+            rows,
+            [Constraint::Length(1)]
+                .into_iter()
+                .chain(widths.iter().map(|&width| Constraint::Length(width))),
+        )
+        .header(header)
+        .column_spacing(1)
+        .highlight_symbol("🥢")
+        .highlight_spacing(HighlightSpacing::Always)
+        .highlight_style(Style::new().cyan().italic().bold());
+
+        // The table shares the list's selection and scrolling.
+        let mut state = TableState::default()
+            .with_selected(self.state.selected())
+            .with_offset(self.state.offset());
+        frame.render_widget(block, rect);
+        frame.render_stateful_widget(table, inner, &mut state);
+        *self.state.offset_mut() = state.offset();
+        let track = Rect {
+            y: self.areas.list.y,
+            height: self.areas.list.height,
+            ..inner_rows(rect)
+        };
+        render_scrollbar(frame, track, self.hits.len(), state.offset());
+    }
+
+    /// The content of a table cell for `App::hits[hit]`, on one line.
+    fn table_cell(&self, position: usize, column: Column) -> Line<'static> {
+        let hit = &self.hits[position];
+        let snippet = &self.snippets[hit.index];
+        let matched = Style::new()
+            .fg(self.config.theme.matched)
+            .bold()
+            .underlined();
+        match column {
+            Column::Index => Line::from(format!("{:02}", position)),
+            Column::Cmd => {
+                let mut line = self.snippet_line(position, Span::from(""));
+                // The mark has a column of its own.
+                line.spans.remove(0);
+                line
+            }
+            Column::Description => {
+                let lines = snippet
+                    .description
+                    .split('\n')
+                    .map(|l| Line::from(l.to_string()))
+                    .collect();
+                let lines = emphasize(lines, &hit.description, matched);
+                lines.into_iter().next().unwrap_or_default()
+            }
+            Column::Tags => {
+                let tags = vec![Line::from(Span::from(snippet.tags.join(" ")).magenta())];
+                emphasize(tags, &hit.tags, matched).remove(0)
+            }
+            Column::Collection => Line::from(snippet.collection.clone().unwrap_or_default()),
+            Column::LastUsed => match snippet.last_used_at {
+                Some(last_used_at) => Line::from(ago(last_used_at, model::now())),
+                None => Line::from("never").dark_gray(),
+            },
+            Column::UseCount => Line::from(snippet.use_count.to_string()).right_aligned(),
+            Column::ExitStatus => match snippet.last_exit {
+                Some(0) => Line::from("0").green(),
+                Some(code) => Line::from(code.to_string()).red(),
+                None => Line::from("-").dark_gray(),
+            },
+        }
+    }
+
+    /// Adds the sort order to the top right of the list, while it applies.
+    fn sort_title<'b>(&self, block: Block<'b>) -> Block<'b> {
+        if self.search_bar.is_empty() {
+            block.title_top(Line::from(format!(" Sort: {} ", self.sort_order)).right_aligned())
+        } else {
+            block
+        }
+    }
+
+    /// A list line for `App::hits[hit]`: its mark, `label`, and the command on one line.
+    fn snippet_line(&self, hit: usize, label: Span<'static>) -> Line<'static> {
+        let hit = &self.hits[hit];
+        let snippet = &self.snippets[hit.index];
+        let matched = Style::new()
+            .fg(self.config.theme.matched)
+            .bold()
+            .underlined();
+        let mark = if self.marked.contains(&hit.index) {
+            Span::from("● ").magenta()
+        } else {
+            Span::from("  ")
+        };
+        let mut line = Line::from(vec![mark, label]);
+        if snippet.is_runbook() {
+            line.push_span(Span::from("▶ ").yellow());
+        }
+        let cmd = highlight(
+            &snippet.script(),
+            snippet.shell.as_deref(),
+            &self.config.theme,
+        );
+        let cmd = emphasize(cmd, &hit.cmd, matched);
+        for (i, cmd_line) in cmd.into_iter().enumerate() {
+            if i > 0 {
+                line.push_span(" ");
+            }
+            line.spans.extend(cmd_line.spans);
         }
+        line
+    }
+
+    fn view_tree(&mut self, frame: &mut Frame, rect: Rect) {
+        // This is safe. It's only called while the tree is shown.
+        let tree = self.tree.as_ref().unwrap();
+        let mut title = match &tree.scope {
+            Some(scope) => format!(" {} ", scope),
+            None => " Categories ".to_string(),
+        };
+        if !self.marked.is_empty() {
+            title.push_str(&format!("· {} marked ", self.marked.len()));
+        }
+        let block = Block::bordered()
+            .title(title)
+            .border_type(BorderType::Rounded);
+        let block = self.sort_title(block);
+        let inner = block.inner(rect);
+        self.areas.list = inner;
+
+        let items: Vec<ListItem> = tree
+            .rows
+            .iter()
+            .map(|row| {
+                let indent = Span::from("  ".repeat(row.depth()));
+                match row {
+                    Row::Category {
+                        path,
+                        count,
+                        expanded,
+                        ..
+                    } => {
+                        let name = path.rsplit('/').next().unwrap_or(path);
+                        let arrow = if *expanded { "▾" } else { "▸" };
+                        ListItem::new(Line::from(vec![
+                            Span::from("  "),
+                            indent,
+                            Span::from(format!("{} {} ", arrow, name)).yellow().bold(),
+                            Span::from(format!("({})", count)).dark_gray(),
+                        ]))
+                    }
+                    Row::Snippet { hit, .. } => {
+                        let mut line = self.snippet_line(*hit, indent);
+                        // Keep the mark in front of the indentation.
+                        line.spans.swap(0, 1);
+                        ListItem::new(line)
+                    }
+                }
+            })
+            .collect();
+
+        let list = List::new(items)
+            .highlight_symbol("🥢")
+            .highlight_spacing(HighlightSpacing::Always)
+            .highlight_style(Style::new().cyan().italic().bold());
+
+        frame.render_widget(block, rect);
+        // This is safe. It's only called while the tree is shown.
+        let tree = self.tree.as_mut().unwrap();
+        frame.render_stateful_widget(list, inner, &mut tree.state);
+        render_scrollbar(
+            frame,
+            inner_rows(rect),
+            tree.rows.len(),
+            tree.state.offset(),
+        );
+    }
+
+    /// The details of a category row: its path and how many snippets it holds.
+    fn view_category_details(&mut self, frame: &mut Frame, inner: Rect, path: &str) {
+        let count = self
+            .hits
+            .iter()
+            .filter(|hit| tree::in_scope(self.snippets[hit.index].category.as_deref(), path))
+            .count();
+        let mut content = Text::default();
+        content.push_line("[Category]".cyan().bold());
+        content.push_line(Span::from(path.to_string()).yellow());
+        content.push_line("[Snippets]".cyan().bold());
+        content.push_line(count.to_string());
+        frame.render_widget(Paragraph::new(content).wrap(Wrap { trim: false }), inner);
     }
 
     fn view_snippet_details(&mut self, frame: &mut Frame, rect: Rect) {
@@ -87,22 +450,110 @@ impl<'a> App<'a> {
             .border_type(BorderType::Rounded)
             .padding(Padding::horizontal(2));
         let inner = block.inner(rect);
+        self.areas.details = rect;
 
         frame.render_widget(block, rect);
 
-        let index = self.state.selected().unwrap();
-
-        if let Some(snippet) = self.snippets.get(index) {
+        if let Some(path) = self.selected_category() {
+            let path = path.to_string();
+            self.view_category_details(frame, inner, &path);
+        } else if let Some(hit) = self.selected_hit() {
+            let snippet = &self.snippets[hit.index];
+            let matched = Style::new()
+                .fg(self.config.theme.matched)
+                .bold()
+                .underlined();
             let mut content = Text::default();
 
-            content.push_line("[Command]".cyan().bold());
-            content.extend(snippet.cmd.lines());
+            let cmd = highlight(
+                &snippet.script(),
+                snippet.shell.as_deref(),
+                &self.config.theme,
+            );
+            let cmd = emphasize(cmd, &hit.cmd, matched);
+            if snippet.is_runbook() {
+                // A checklist of the steps, each followed by its command lines.
+                content.push_line("[Steps]".cyan().bold());
+                let mut cmd = cmd.into_iter();
+                for (i, step) in snippet.steps.iter().enumerate() {
+                    content.push_line(Line::from(vec![
+                        Span::from(format!("☐ {}. ", i + 1)).yellow(),
+                        Span::from(step.description.clone()),
+                    ]));
+                    for mut line in cmd.by_ref().take(step.cmd.split('\n').count()) {
+                        line.spans.insert(0, Span::from("    "));
+                        content.push_line(line);
+                    }
+                }
+            } else {
+                content.push_line("[Command]".cyan().bold());
+                content.extend(cmd);
+            }
+            if reference::has_references(&snippet.script()) {
+                content.push_line("[Expanded]".cyan().bold());
+                match self.expanded_script(hit.index) {
+                    Ok(expanded) => content.extend(highlight(
+                        &expanded,
+                        snippet.shell.as_deref(),
+                        &self.config.theme,
+                    )),
+                    Err(err) => content.push_line(Span::from(err.to_string()).red()),
+                }
+            }
             content.push_line("[Description]".cyan().bold());
-            content.extend(snippet.description.lines());
+            let description = snippet
+                .description
+                .split('\n')
+                .map(|l| Line::from(l.to_string()))
+                .collect();
+            content.extend(emphasize(description, &hit.description, matched));
+            if !snippet.tags.is_empty() {
+                content.push_line("[Tags]".cyan().bold());
+                let tags = vec![Line::from(Span::from(snippet.tags.join(" ")).magenta())];
+                content.extend(emphasize(tags, &hit.tags, matched));
+            }
+            if let Some(collection) = &snippet.collection {
+                content.push_line("[Collection]".cyan().bold());
+                content.push_line(collection.as_str());
+            }
+            if let Some(category) = &snippet.category {
+                content.push_line("[Category]".cyan().bold());
+                content.push_line(Span::from(category.as_str()).yellow());
+            }
+            content.push_line("[History]".cyan().bold());
+            let now = model::now();
+            let mut history = format!("Created {}", ago(snippet.created_at, now));
+            if snippet.updated_at > snippet.created_at {
+                history.push_str(&format!(", updated {}", ago(snippet.updated_at, now)));
+            }
+            content.push_line(Span::from(history).dark_gray());
+            if let Some(last_used_at) = snippet.last_used_at {
+                content.push_line(
+                    Span::from(format!(
+                        "Used {} time{}, last {}",
+                        snippet.use_count,
+                        if snippet.use_count == 1 { "" } else { "s" },
+                        ago(last_used_at, now)
+                    ))
+                    .dark_gray(),
+                );
+            }
+            if snippet.id != 0 {
+                let reference = format!("{{{{ref:{}}}}}", snippet.id);
+                content.push_line(
+                    Span::from(format!("Included in other commands with {reference}")).dark_gray(),
+                );
+            }
 
-            let content = Paragraph::new(content).wrap(Wrap { trim: true });
+            // Untrimmed, so the indentation of commands and steps shows.
+            let content = Paragraph::new(content).wrap(Wrap { trim: false });
+            // Scroll no further than to the last page.
+            let lines = content.line_count(inner.width);
+            let max_scroll = lines.saturating_sub(inner.height as usize);
+            self.details_scroll = self.details_scroll.min(max_scroll as u16);
 
-            frame.render_widget(content, inner);
+            frame.render_widget(content.scroll((self.details_scroll, 0)), inner);
+            render_scrollbar(frame, inner_rows(rect), lines, self.details_scroll as usize);
         } else {
             let nothing =
                 Paragraph::new(Line::from("There's nothing. Let's select one, and the details will be displayed here OwO.").bold())
@@ -124,34 +575,502 @@ impl<'a> App<'a> {
         );
 
         frame.render_widget(block, rect);
-        frame.render_widget(editor.widget(), inner);
+
+        // Warnings go below the editor, with a marker left of each line they are about.
+        let (inner, warnings, gutter) = match &self.syntax_report {
+            Some(report) => {
+                let height = report.warnings.len().min(6) as u16 + 2;
+                let [inner, warnings] =
+                    Layout::vertical([Constraint::Fill(1), Constraint::Length(height)])
+                        .areas(inner);
+                let [gutter, inner] =
+                    Layout::horizontal([Constraint::Length(2), Constraint::Fill(1)]).areas(inner);
+                editor.set_line_number_style(Style::new().dark_gray());
+                (inner, warnings, gutter)
+            }
+            None => {
+                editor.remove_line_number();
+                (inner, Rect::default(), Rect::default())
+            }
+        };
+        frame.render_widget(&*editor, inner);
+
+        // Follows the editor's scrolling, which only moves to keep the cursor in view.
+        let cursor = editor.cursor().0 as u16;
+        if cursor < self.editor_top {
+            self.editor_top = cursor;
+        } else if self.editor_top + inner.height <= cursor {
+            self.editor_top = cursor + 1 - inner.height;
+        }
+
+        let Some(report) = &self.syntax_report else {
+            return;
+        };
+        for warning in &report.warnings {
+            if let Some(row) = (warning.line as u16)
+                .checked_sub(self.editor_top)
+                .filter(|&row| row < gutter.height)
+            {
+                let marker = Rect::new(gutter.x, gutter.y + row, 1, 1);
+                frame.render_widget(Span::from("▶").red().bold(), marker);
+            }
+        }
+
+        let lines: Vec<Line> = report
+            .warnings
+            .iter()
+            .map(|warning| {
+                Line::from(vec![
+                    Span::from(format!("{:>3} ", warning.line + 1)).red().bold(),
+                    Span::from(warning.message.as_str()),
+                ])
+            })
+            .collect();
+        let save = self
+            .keymap
+            .editor
+            .iter()
+            .find(|binding| binding.action == Action::Save)
+            .map(|binding| binding.labels()[0].clone())
+            .unwrap_or_default();
+        let block = Block::bordered()
+            .title(format!(" {} warnings ", report.warnings.len()))
+            .title_bottom(format!(" <{}> again to save anyway, or edit to fix ", save))
+            .border_type(BorderType::Rounded)
+            .border_style(Style::new().red());
+        let content = Paragraph::new(lines).block(block);
+        frame.render_widget(content, warnings);
     }
 
+    /// Groups of duplicates on the left, the snippets of the selected group side by side.
+    fn view_dedupe(&mut self, frame: &mut Frame, rect: Rect) {
+        let stacked = self.is_stacked(rect.width);
+        // This is safe. It's only called while the view is open.
+        let dedupe = self.dedupe.as_mut().unwrap();
+        if dedupe.groups.is_empty() {
+            let block = Block::bordered()
+                .title(" Duplicates ")
+                .border_type(BorderType::Rounded);
+            let nothing = Paragraph::new(Line::from("No duplicates found.").bold())
+                .centered()
+                .block(block);
+            frame.render_widget(nothing, rect);
+            return;
+        }
+
+        let constraints = [Constraint::Percentage(30), Constraint::Fill(1)];
+        let [groups, members] = if stacked {
+            Layout::vertical(constraints).areas(rect)
+        } else {
+            Layout::horizontal(constraints).areas(rect)
+        };
+
+        let items: Vec<ListItem> = dedupe
+            .groups
+            .iter()
+            .map(|group| {
+                let cmd = dedupe::normalize(&self.snippets[group[0]].script());
+                ListItem::new(Line::from(vec![
+                    Span::from(format!("{}× ", group.len())).yellow(),
+                    Span::from(cmd),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!(" {} groups of duplicates ", dedupe.groups.len()))
+                    .border_type(BorderType::Rounded),
+            )
+            .highlight_symbol("🥢")
+            .highlight_spacing(HighlightSpacing::Always)
+            .highlight_style(Style::new().cyan().italic().bold());
+        frame.render_stateful_widget(list, groups, &mut dedupe.state);
+
+        let Some(group) = dedupe.state.selected().and_then(|i| dedupe.groups.get(i)) else {
+            return;
+        };
+        let columns = Layout::horizontal(vec![Constraint::Fill(1); group.len()]).split(members);
+        for (&index, &column) in group.iter().zip(columns.iter()) {
+            let snippet = &self.snippets[index];
+            let mut content = Text::default();
+            content.push_line("[Command]".cyan().bold());
+            content.extend(highlight(
+                &snippet.script(),
+                snippet.shell.as_deref(),
+                &self.config.theme,
+            ));
+            content.push_line("[Description]".cyan().bold());
+            content.extend(
+                snippet
+                    .description
+                    .lines()
+                    .map(|l| Line::from(l.to_string())),
+            );
+            if !snippet.tags.is_empty() {
+                content.push_line("[Tags]".cyan().bold());
+                content.push_line(Span::from(snippet.tags.join(" ")).magenta());
+            }
+            let block = Block::bordered()
+                .title(format!(" used {} times ", snippet.use_count))
+                .border_type(BorderType::Rounded)
+                .padding(Padding::horizontal(1));
+            let content = Paragraph::new(content)
+                .block(block)
+                .wrap(Wrap { trim: false });
+            frame.render_widget(content, column);
+        }
+    }
+
+    /// The jobs above, the output of the selected one below.
+    fn view_jobs(&mut self, frame: &mut Frame, rect: Rect) {
+        // This is safe. It's only called while the panel is open.
+        let panel = self.jobs_panel.as_mut().unwrap();
+        if self.jobs.is_empty() {
+            let block = Block::bordered()
+                .title(" Jobs ")
+                .border_type(BorderType::Rounded);
+            let nothing = Paragraph::new(Line::from("No jobs yet.").bold())
+                .centered()
+                .block(block);
+            frame.render_widget(nothing, rect);
+            return;
+        }
+
+        let height = (self.jobs.len() as u16 + 2).min(rect.height / 3).max(3);
+        let [jobs, output] =
+            Layout::vertical([Constraint::Length(height), Constraint::Fill(1)]).areas(rect);
+
+        let running = self.jobs.iter().filter(|job| job.is_running()).count();
+        let items: Vec<ListItem> = self
+            .jobs
+            .iter()
+            .enumerate()
+            .map(|(i, job)| {
+                let status = match job.exit() {
+                    None => Span::from("running").yellow(),
+                    Some(Some(0)) => Span::from("exit 0").green(),
+                    Some(Some(code)) => Span::from(format!("exit {code}")).red(),
+                    Some(None) => Span::from("killed").red(),
+                };
+                let cmd = job.snippet.cmd.lines().next().unwrap_or_default();
+                ListItem::new(Line::from(vec![
+                    Span::from(format!("{:>3} ", i + 1)).dark_gray(),
+                    Span::from(format!("{:>7}  ", job.pid)),
+                    Span::from(format!("{:<8}", status.content)).style(status.style),
+                    Span::from(format!("{:>7}  ", runtime(job.runtime().as_secs()))),
+                    Span::from(cmd.to_string()),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!(" Jobs: {running} running "))
+                    .border_type(BorderType::Rounded),
+            )
+            .highlight_symbol("🥢")
+            .highlight_spacing(HighlightSpacing::Always)
+            .highlight_style(Style::new().cyan().italic().bold());
+        frame.render_stateful_widget(list, jobs, &mut panel.state);
+        render_scrollbar(
+            frame,
+            inner_rows(jobs),
+            self.jobs.len(),
+            panel.state.offset(),
+        );
+
+        let Some(job) = panel.state.selected().and_then(|i| self.jobs.get(i)) else {
+            return;
+        };
+        let block = Block::bordered()
+            .title(" Output ")
+            .title_bottom(Line::from(format!(" {} ", job.log.display())).dark_gray())
+            .border_type(BorderType::Rounded)
+            .padding(Padding::horizontal(1));
+        let inner = block.inner(output);
+        self.areas.details = output;
+        frame.render_widget(block, output);
+
+        // Output follows the last lines, unless scrolled back.
+        let lines = job.output();
+        let max_scroll = lines.len().saturating_sub(inner.height as usize) as u16;
+        if panel.follow || panel.scroll >= max_scroll {
+            panel.scroll = max_scroll;
+            panel.follow = true;
+        }
+        let content = Paragraph::new(
+            lines
+                .iter()
+                .map(|line| Line::from(line.as_str()))
+                .collect::<Vec<_>>(),
+        )
+        .scroll((panel.scroll, 0));
+        frame.render_widget(content, inner);
+        render_scrollbar(
+            frame,
+            inner_rows(output),
+            lines.len(),
+            panel.scroll as usize,
+        );
+    }
+
+    /// Shows the hints of the current mode's bindings. Each hint is a button for its action.
     fn view_instructions(&mut self, frame: &mut Frame, rect: Rect) {
         let inner = Block::new().padding(Padding::horizontal(1)).inner(rect);
-        let instructions = Line::from(vec![
-            Span::from("<Enter> Execute").bold().on_cyan(),
-            Span::from(" | "),
-            Span::from("Ctrl").bold().on_cyan(),
-            Span::from(" + "),
-            Span::from("<a> Add").on_dark_gray(),
-            Span::from(" "),
-            Span::from("<r> Remove").on_dark_gray(),
-            Span::from(" "),
-            Span::from("<e> Edit").on_dark_gray(),
-            Span::from(" "),
-            Span::from("<s> Save").on_dark_gray(),
-            Span::from(" "),
-            Span::from("<c> quit or cancel").on_dark_gray(),
-            Span::from(" "),
-            Span::from("<enter> copy").on_dark_gray(),
-        ])
-        .white()
-        .alignment(Alignment::Left);
+        let mode = self.mode();
+        let hints: Vec<&Binding> = self
+            .keymap
+            .bindings(mode)
+            .iter()
+            .filter(|binding| match binding.hint {
+                Hint::Always => true,
+                Hint::WhenMarked => !self.marked.is_empty(),
+                Hint::Never => false,
+            })
+            .collect();
+
+        let hint_spans = |binding: &Binding| {
+            [
+                Span::from(format!("<{}>", binding.labels()[0]))
+                    .bold()
+                    .on_cyan(),
+                Span::from(format!(" {} ", binding.description)).on_dark_gray(),
+                Span::from(" "),
+            ]
+        };
+        let hint_width = |binding: &Binding| -> u16 {
+            hint_spans(binding).iter().map(|s| s.width() as u16).sum()
+        };
+
+        let mut spans = Vec::new();
+        if self.config.keymap == KeymapPreset::Vim && matches!(mode, Mode::List | Mode::Normal) {
+            let indicator = match mode {
+                Mode::Normal => Span::from(" NORMAL ").bold().black().on_green(),
+                _ => Span::from(" INSERT ").bold().black().on_yellow(),
+            };
+            spans.extend([indicator, Span::from(" ")]);
+        }
+        let mut x = inner.x + spans.iter().map(|s| s.width() as u16).sum::<u16>();
+
+        // Drop hints from the end until they fit, but keep the way to the help.
+        let help_width: u16 = hints
+            .iter()
+            .filter(|b| b.action == Action::Help)
+            .map(|b| hint_width(b))
+            .sum();
+        let mut width = x - inner.x + help_width;
+        let hints: Vec<&Binding> = hints
+            .into_iter()
+            .filter(|binding| {
+                if binding.action == Action::Help {
+                    return true;
+                }
+                width += hint_width(binding);
+                width <= inner.width
+            })
+            .collect();
+
+        for binding in hints {
+            let area = Rect::new(x, inner.y, hint_width(binding), 1).intersection(inner);
+            self.areas.buttons.push((area, binding.action));
+            x = x.saturating_add(hint_width(binding));
+            spans.extend(hint_spans(binding));
+        }
+
+        let instructions = Line::from(spans).white().alignment(Alignment::Left);
 
         frame.render_widget(instructions, inner);
     }
 
+    fn view_help(&mut self, frame: &mut Frame, rect: Rect) {
+        // This is safe. It's only called while the help is open.
+        let mode = self.help.unwrap();
+        let bindings = self.keymap.bindings(mode);
+        let keys_width = bindings
+            .iter()
+            .map(|b| keys_label(b).chars().count())
+            .max()
+            .unwrap_or_default();
+
+        let lines: Vec<Line> = bindings
+            .iter()
+            .map(|binding| {
+                Line::from(vec![
+                    Span::from(format!(
+                        "{:>width$}",
+                        keys_label(binding),
+                        width = keys_width
+                    ))
+                    .cyan()
+                    .bold(),
+                    Span::from("  "),
+                    Span::from(binding.description),
+                ])
+            })
+            .collect();
+        self.help_scroll = self.help_scroll.min(lines.len().saturating_sub(1) as u16);
+
+        let area = centered(rect, 60, 80);
+        let mut block = Block::bordered()
+            .title(format!(" Keys: {} ", mode))
+            .border_type(BorderType::Rounded)
+            .padding(Padding::horizontal(1));
+        if let Some(close) = self.keymap.help.iter().find(|b| b.action == Action::Cancel) {
+            block = block.title_bottom(format!(" <{}> {} ", close.labels()[0], close.description));
+        }
+        let content = Paragraph::new(lines)
+            .block(block)
+            .scroll((self.help_scroll, 0));
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(content, area);
+    }
+
+    fn view_prompt(&mut self, frame: &mut Frame, rect: Rect) {
+        let count = self.targets().len();
+        let hidden = self.hidden_marks();
+        // This is safe. It's only called while a prompt is open.
+        let prompt = self.prompt.as_mut().unwrap();
+        let title = match prompt.action {
+            PromptAction::AddTag => format!(" Add tag to {} snippets ", count),
+            PromptAction::RemoveTag => format!(" Remove tag from {} snippets ", count),
+            PromptAction::MoveToCollection => format!(" Move {} snippets to collection ", count),
+            PromptAction::MoveToCategory => format!(" Move {} snippets to category ", count),
+            PromptAction::RenameCategory => " Rename category ".to_string(),
+            PromptAction::Export => format!(" Export {} snippets to file ", count),
+            PromptAction::ConfirmRemove => match hidden {
+                0 => format!(" Remove {} snippets? (y/N) ", count),
+                hidden => format!(" Remove {count} snippets, {hidden} not shown? (y/N) "),
+            },
+        };
+
+        let [_, area, _] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(3),
+            Constraint::Fill(1),
+        ])
+        .areas(rect);
+        let area = centered(area, 60, 100);
+
+        let block = Block::bordered()
+            .title(title)
+            .border_type(BorderType::Rounded)
+            .padding(Padding::horizontal(1));
+        let inner = block.inner(area);
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+        frame.render_widget(&prompt.input, inner);
+    }
+
+    fn view_fill(&mut self, frame: &mut Frame, rect: Rect) {
+        // This is safe. It's only called while filling in placeholders.
+        let fill = self.fill.as_mut().unwrap();
+        let title = format!(
+            " <{}> {}/{} ",
+            fill.names[fill.current],
+            fill.current + 1,
+            fill.names.len()
+        );
+        let has_choices =
+            !fill.choices.is_empty() || fill.generator.is_some() || fill.generator_error.is_some();
+
+        let height = if has_choices { 14 } else { 3 };
+        let [_, area, _] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(height),
+            Constraint::Fill(1),
+        ])
+        .areas(rect);
+        let area = centered(area, 90, 100);
+
+        let block = Block::bordered()
+            .title(title)
+            .border_type(BorderType::Rounded)
+            .padding(Padding::horizontal(1));
+        let inner = block.inner(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+
+        let [input, choices] =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(inner);
+        let typed = if fill.state.selected().is_none() {
+            Style::new().bold()
+        } else {
+            Style::new().dim()
+        };
+        fill.input.set_style(typed);
+        frame.render_widget(&fill.input, input);
+
+        if !has_choices {
+            return;
+        }
+        let [_, choices] =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(choices);
+        if let Some(err) = &fill.generator_error {
+            let content = Paragraph::new(Line::from(err.as_str()).red()).wrap(Wrap { trim: true });
+            frame.render_widget(content, choices);
+        } else if fill.generator.is_some() {
+            frame.render_widget(
+                Line::from("Running generator...").dark_gray().italic(),
+                choices,
+            );
+        } else {
+            let matched = Style::new()
+                .fg(self.config.theme.matched)
+                .bold()
+                .underlined();
+            let items: Vec<ListItem> = fill
+                .matches
+                .iter()
+                .map(|(i, positions)| {
+                    let line = Line::from(fill.choices[*i].clone());
+                    ListItem::new(emphasize(vec![line], positions, matched))
+                })
+                .collect();
+            let list = List::new(items)
+                .highlight_symbol("> ")
+                .highlight_spacing(HighlightSpacing::Always)
+                .highlight_style(Style::new().cyan().bold());
+            frame.render_stateful_widget(list, choices, &mut fill.state);
+        }
+    }
+
+    /// The command with the placeholders filled in so far, exactly as it will
+    /// be executed or copied.
+    fn view_fill_preview(&mut self, frame: &mut Frame, rect: Rect) {
+        let block = Block::bordered()
+            .title(" Preview ")
+            .border_type(BorderType::Rounded)
+            .padding(Padding::horizontal(2));
+        let inner = block.inner(rect);
+        self.areas.details = rect;
+        frame.render_widget(block, rect);
+
+        // This is safe. It's only called while filling in placeholders.
+        let fill = self.fill.as_ref().unwrap();
+        let (preview, positions) = fill.preview();
+        let shell = fill.snippet.shell.as_deref();
+        let filled = Style::new().bg(self.config.theme.filled);
+        let lines = emphasize(
+            highlight(&preview, shell, &self.config.theme),
+            &positions,
+            filled,
+        );
+        let content = Paragraph::new(lines).wrap(Wrap { trim: false });
+
+        frame.render_widget(content, inner);
+    }
+
+    fn view_status_msg(&self, frame: &mut Frame, rect: Rect) {
+        let inner = Block::new().padding(Padding::horizontal(1)).inner(rect);
+        let msg = self.status_msg.as_ref().unwrap();
+        let content = Line::from(msg.as_str()).green();
+
+        frame.render_widget(content, inner);
+    }
+
     fn view_error_msg(&self, frame: &mut Frame, rect: Rect) {
         let inner = Block::new().padding(Padding::horizontal(1)).inner(rect);
         let msg = self.error_msg.as_ref().unwrap();
@@ -160,3 +1079,452 @@ impl<'a> App<'a> {
         frame.render_widget(content, inner);
     }
 }
+
+/// All keys of a binding, as listed in the help.
+fn keys_label(binding: &Binding) -> String {
+    binding.labels().join(", ")
+}
+
+/// A rect of the given percentages of `rect`, centered in it.
+fn centered(rect: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [_, area, _] = Layout::vertical([
+        Constraint::Fill(1),
+        Constraint::Percentage(percent_y),
+        Constraint::Fill(1),
+    ])
+    .areas(rect);
+    let [_, area, _] = Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Percentage(percent_x),
+        Constraint::Fill(1),
+    ])
+    .areas(area);
+    area
+}
+
+/// How long before `now` the time `then` was, like `3 days ago`. Both are in
+/// seconds since the Unix epoch.
+fn ago(then: u64, now: u64) -> String {
+    let secs = now.saturating_sub(then);
+    let (n, unit) = match secs {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86_399 => (secs / 3600, "hour"),
+        86_400..=2_591_999 => (secs / 86_400, "day"),
+        2_592_000..=31_535_999 => (secs / 2_592_000, "month"),
+        _ => (secs / 31_536_000, "year"),
+    };
+    format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" })
+}
+
+/// A duration like `45s`, `3m05s` or `1h02m`, from seconds.
+fn runtime(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+fn column_title(column: Column) -> &'static str {
+    match column {
+        Column::Index => "#",
+        Column::Cmd => "Command",
+        Column::Description => "Description",
+        Column::Tags => "Tags",
+        Column::Collection => "Collection",
+        Column::LastUsed => "Last used",
+        Column::UseCount => "Uses",
+        Column::ExitStatus => "Exit",
+    }
+}
+
+/// Widths for columns of the given natural widths in `available` columns.
+/// Other columns keep their natural width; the `flexible` ones share what is
+/// left, the narrow ones first so that they needn't be cut, and the last of them
+/// gets any space to spare.
+fn fit(naturals: &[u16], flexible: &[bool], available: u16) -> Vec<u16> {
+    let mut widths = naturals.to_vec();
+    let fixed: u16 = (0..naturals.len())
+        .filter(|&i| !flexible[i])
+        .map(|i| naturals[i])
+        .sum();
+    let mut left = available.saturating_sub(fixed);
+
+    let mut flex: Vec<usize> = (0..naturals.len()).filter(|&i| flexible[i]).collect();
+    flex.sort_by_key(|&i| naturals[i]);
+    for (n, &i) in flex.iter().enumerate() {
+        let share = left / (flex.len() - n) as u16;
+        widths[i] = naturals[i].min(share);
+        left -= widths[i];
+    }
+    if let Some(last) = flexible.iter().rposition(|&f| f) {
+        widths[last] += left;
+    }
+    widths
+}
+
+/// `line` cut to `width` columns, with an ellipsis if anything was cut.
+fn truncate(line: Line<'static>, width: usize) -> Line<'static> {
+    if line.width() <= width {
+        return line;
+    }
+    // Not even the ellipsis fits.
+    if width == 0 {
+        return Line::default().alignment(line.alignment.unwrap_or_default());
+    }
+    let mut left = width.saturating_sub(1);
+    let mut truncated = Line::default().alignment(line.alignment.unwrap_or_default());
+    for span in line.spans {
+        let mut content = String::new();
+        for c in span.content.chars() {
+            let w = c.width().unwrap_or_default();
+            if w > left {
+                left = 0;
+                break;
+            }
+            left -= w;
+            content.push(c);
+        }
+        truncated.push_span(Span::styled(content, span.style));
+        if left == 0 {
+            break;
+        }
+    }
+    truncated.push_span("…");
+    truncated
+}
+
+/// The rows of `rect` inside its border, where its scrollbar goes.
+fn inner_rows(rect: Rect) -> Rect {
+    rect.inner(Margin {
+        vertical: 1,
+        horizontal: 0,
+    })
+}
+
+/// Draws a scrollbar on the right of `track`, if `total` rows don't fit in it
+/// and the first one shown is at `offset`.
+fn render_scrollbar(frame: &mut Frame, track: Rect, total: usize, offset: usize) {
+    let height = track.height as usize;
+    if total <= height {
+        return;
+    }
+    let mut state = ScrollbarState::new(total - height + 1)
+        .position(offset)
+        .viewport_content_length(height);
+    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None)
+        .track_symbol(None)
+        .thumb_style(Style::new().cyan());
+    frame.render_stateful_widget(scrollbar, track, &mut state);
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use ratatui::{backend::TestBackend, Terminal};
+    use unicode_width::UnicodeWidthStr;
+
+    use super::*;
+    use crate::tui::{
+        model::Snippet,
+        update::{JobsMsg, Msg},
+    };
+
+    fn app() -> App<'static> {
+        let mut app = App::new();
+        let now = model::now();
+        app.snippets = vec![
+            Snippet {
+                cmd: "git status --short".into(),
+                description: "Show changed files".into(),
+                created_at: now,
+                updated_at: now,
+                ..Default::default()
+            },
+            Snippet {
+                cmd: "docker ps -a".into(),
+                description: "List containers".into(),
+                created_at: now,
+                updated_at: now,
+                ..Default::default()
+            },
+        ];
+        app.search_snippet().unwrap();
+        app
+    }
+
+    /// Renders `app` on a `width`×`height` terminal, one string per row.
+    fn render(app: &mut App, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| app.view(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..height)
+            .map(|y| {
+                let mut row = String::new();
+                let mut skip = 0;
+                for x in 0..width {
+                    // Wide chars cover the cells after them.
+                    if skip > 0 {
+                        skip -= 1;
+                        continue;
+                    }
+                    let symbol = buffer.get(x, y).symbol();
+                    skip = symbol.width().saturating_sub(1);
+                    row.push_str(symbol);
+                }
+                row.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn side_by_side_when_wide() {
+        let mut app = app();
+        assert_eq!(
+            render(&mut app, 100, 12),
+            [
+                "╭ Fuzzy ─────────────────────────────────────────╮╭────────────────────────────────────────────────╮",
+                "│   Type to search                               ││  [Command]                                     │",
+                "╰────────────────────────────────────────────────╯│  git status --short                            │",
+                "╭──────────────────────────────── Sort: Priority ╮│  [Description]                                 │",
+                "│🥢  00 git status --short                       ││  Show changed files                            │",
+                "│    01 docker ps -a                             ││  [History]                                     │",
+                "│                                                ││  Created just now                              │",
+                "│                                                ││                                                │",
+                "│                                                ││                                                │",
+                "│                                                ││                                                │",
+                "╰────────────────────────────────────────────────╯╰────────────────────────────────────────────────╯",
+                " <Enter> Execute  <Ctrl-Enter> Copy  <Ctrl-r> Remove  <Ctrl-a> Add  <Ctrl-e> Edit  <F1> Help",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stacked_when_narrow() {
+        let mut app = app();
+        // The details don't fit, so they get a scrollbar.
+        assert_eq!(
+            render(&mut app, 60, 16),
+            [
+                "╭ Fuzzy ───────────────────────────────────────────────────╮",
+                "│   Type to search                                         │",
+                "╰──────────────────────────────────────────────────────────╯",
+                "╭────────────────────────────────────────── Sort: Priority ╮",
+                "│🥢  00 git status --short                                 │",
+                "│    01 docker ps -a                                       │",
+                "│                                                          │",
+                "╰──────────────────────────────────────────────────────────╯",
+                "╭──────────────────────────────────────────────────────────╮",
+                "│  [Command]                                               █",
+                "│  git status --short                                      █",
+                "│  [Description]                                           █",
+                "│  Show changed files                                      █",
+                "│  [History]                                               │",
+                "╰──────────────────────────────────────────────────────────╯",
+                " <Enter> Execute  <Ctrl-Enter> Copy  <F1> Help",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stacks_below_the_configured_width() {
+        let mut app = app();
+        app.config.layout.stack_below = 120;
+        let rows = render(&mut app, 100, 16);
+        let list = rows.iter().position(|row| row.contains("docker ps -a"));
+        let preview = rows.iter().position(|row| row.contains("[Command]"));
+        assert!(list < preview, "{rows:#?}");
+    }
+
+    #[tokio::test]
+    async fn hidden_preview() {
+        let mut app = app();
+        app.show_preview = false;
+        assert_eq!(
+            render(&mut app, 60, 10),
+            [
+                "╭ Fuzzy ───────────────────────────────────────────────────╮",
+                "│   Type to search                                         │",
+                "╰──────────────────────────────────────────────────────────╯",
+                "╭────────────────────────────────────────── Sort: Priority ╮",
+                "│🥢  00 git status --short                                 │",
+                "│    01 docker ps -a                                       │",
+                "│                                                          │",
+                "│                                                          │",
+                "╰──────────────────────────────────────────────────────────╯",
+                " <Enter> Execute  <Ctrl-Enter> Copy  <F1> Help",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn split_ratio() {
+        let mut app = app();
+        app.config.layout.split = 30;
+        assert_eq!(
+            render(&mut app, 100, 12),
+            [
+                "╭ Fuzzy ─────────────────────╮╭────────────────────────────────────────────────────────────────────╮",
+                "│   Type to search           ││  [Command]                                                         │",
+                "╰────────────────────────────╯│  git status --short                                                │",
+                "╭──────────── Sort: Priority ╮│  [Description]                                                     │",
+                "│🥢  00 git status --short   ││  Show changed files                                                │",
+                "│    01 docker ps -a         ││  [History]                                                         │",
+                "│                            ││  Created just now                                                  │",
+                "│                            ││                                                                    │",
+                "│                            ││                                                                    │",
+                "│                            ││                                                                    │",
+                "╰────────────────────────────╯╰────────────────────────────────────────────────────────────────────╯",
+                " <Enter> Execute  <Ctrl-Enter> Copy  <Ctrl-r> Remove  <Ctrl-a> Add  <Ctrl-e> Edit  <F1> Help",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn too_small() {
+        let mut app = app();
+        assert_eq!(
+            render(&mut app, 30, 8),
+            [
+                "",
+                "",
+                "",
+                "      Terminal too small",
+                "             30×8",
+                "     Needs at least 40×10",
+                "",
+                "",
+            ]
+        );
+        // Nothing to click on.
+        assert!(app.areas.buttons.is_empty());
+        assert_eq!(app.areas.list, Rect::default());
+    }
+
+    /// Thirty snippets with twenty lines of description each.
+    fn long_app() -> App<'static> {
+        let mut app = app();
+        app.snippets = (0..30)
+            .map(|i| Snippet {
+                cmd: format!("echo {i}"),
+                description: (0..20)
+                    .map(|line| format!("line {line}"))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                created_at: model::now(),
+                ..Default::default()
+            })
+            .collect();
+        app.search_snippet().unwrap();
+        app
+    }
+
+    #[tokio::test]
+    async fn details_scroll_to_the_last_page() {
+        let mut app = long_app();
+        app.update(Msg::ScrollDetails(100));
+        let rows = render(&mut app, 100, 14);
+        // 25 lines of details in 11 rows.
+        assert_eq!(app.details_scroll, 14);
+        assert!(rows[11].contains("Created just now"));
+        // The scrollbar thumb is at the bottom of the details.
+        assert!(rows[1].ends_with('│'));
+        assert!(rows[11].ends_with('█'));
+    }
+
+    #[tokio::test]
+    async fn page_through_the_list() {
+        let mut app = long_app();
+        render(&mut app, 100, 14);
+        let mut press = |code, modifiers| {
+            if let Some(msg) = app.handle_key(KeyEvent::new(code, modifiers)) {
+                app.update(msg);
+            }
+            app.state.selected()
+        };
+        let (none, ctrl) = (KeyModifiers::NONE, KeyModifiers::CONTROL);
+        // Eight rows in view.
+        assert_eq!(press(KeyCode::PageDown, none), Some(8));
+        assert_eq!(press(KeyCode::End, ctrl), Some(29));
+        assert_eq!(press(KeyCode::PageDown, none), Some(29));
+        assert_eq!(press(KeyCode::PageUp, none), Some(21));
+        // Plain `Home` is for the search bar.
+        assert_eq!(press(KeyCode::Home, none), Some(21));
+        assert_eq!(press(KeyCode::Home, ctrl), Some(0));
+    }
+
+    #[tokio::test]
+    async fn jobs_panel() {
+        let mut app = app();
+        app.snippets[0].cmd = "printf 'one\\ntwo\\n'; exit 2".into();
+        app.update(Msg::RunInBackground);
+        while app.jobs[0].is_running() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        app.update(Msg::Jobs(JobsMsg::Open));
+
+        let pid = app.jobs[0].pid;
+        let rows = render(&mut app, 60, 12);
+        app.jobs[0].remove_log();
+        assert_eq!(
+            rows[0],
+            "╭ Jobs: 0 running ─────────────────────────────────────────╮"
+        );
+        assert!(rows[1].starts_with(&format!("│🥢  1 {pid:>7}  exit 2       0s  printf")));
+        assert_eq!(
+            rows[4],
+            "│ one                                                      │"
+        );
+        assert_eq!(
+            rows[5],
+            "│ two                                                      │"
+        );
+    }
+
+    #[test]
+    fn fit_columns() {
+        let flexible = [false, true, false, true];
+        // Room to spare goes to the last flexible column.
+        assert_eq!(fit(&[2, 10, 4, 6], &flexible, 30), [2, 10, 4, 14]);
+        // The narrow flexible column keeps its width, the wide one is cut.
+        assert_eq!(fit(&[2, 10, 4, 6], &flexible, 18), [2, 6, 4, 6]);
+        // Without room, flexible columns get nothing and fixed ones keep theirs.
+        assert_eq!(fit(&[2, 10, 4, 6], &flexible, 0), [2, 0, 4, 0]);
+        assert_eq!(fit(&[2, 10, 4, 6], &flexible, 5), [2, 0, 4, 0]);
+        // All of them want more than they can have: an even share each.
+        assert_eq!(fit(&[10, 20, 30], &[true; 3], 31), [10, 10, 11]);
+        assert_eq!(fit(&[30, 20, 40], &[true; 3], 12), [4, 4, 4]);
+        assert_eq!(fit(&[30, 20, 40], &[true; 3], 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn truncate_lines() {
+        let cut = |text: &str, width| {
+            let line = truncate(Line::from(text.to_string()), width);
+            (line.to_string(), line.width())
+        };
+        assert_eq!(cut("git status", 20), ("git status".to_string(), 10));
+        assert_eq!(cut("git status", 10), ("git status".to_string(), 10));
+        assert_eq!(cut("git status", 6), ("git s…".to_string(), 6));
+        assert_eq!(cut("git status", 1), ("…".to_string(), 1));
+        assert_eq!(cut("git status", 0), (String::new(), 0));
+        // A wide char that doesn't fit is left out whole.
+        assert_eq!(cut("日本語", 6), ("日本語".to_string(), 6));
+        assert_eq!(cut("日本語", 5), ("日本…".to_string(), 5));
+        assert_eq!(cut("日本語", 4), ("日…".to_string(), 3));
+        assert_eq!(cut("日本語", 1), ("…".to_string(), 1));
+
+        // Styles are kept, and spans after the cut dropped.
+        let line = Line::from(vec![
+            Span::from("ls ").bold(),
+            Span::from("-la"),
+            Span::from(" /"),
+        ]);
+        let line = truncate(line, 5);
+        assert_eq!(line.to_string(), "ls -…");
+        assert_eq!(line.spans[0], Span::from("ls ").bold());
+    }
+}